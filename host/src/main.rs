@@ -3,8 +3,21 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
+use std::collections::HashSet;
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
-use zk_epdcalc::start_prover_service;
+use zk_epdcalc::{
+    start_prover_service, DidKey, DidOyd, DidResolver, DidResolverRegistry, DidWeb, FsProofCache,
+    IssuerKey, KeyResolver, ProofCache, ProvingOptions, SqliteTaskStore, StaticDidResolver,
+    StaticKeyResolver, TaskStore, VolatileTaskStore, WebhookOptions,
+};
+
+/// Hours the results of asynchronous tasks are kept.
+const DEFAULT_RETENTION_HOURS: u64 = 7 * 24;
+const DEFAULT_MAX_FINISHED_TASKS: usize = 10_000;
 
 #[tokio::main]
 async fn main() {
@@ -16,7 +29,98 @@ async fn main() {
     let server_address = "0.0.0.0:3000";
     println!("Starting Server on {}", server_address);
 
-    let (app, handle) = start_prover_service(zk_building_part::builder());
+    let emission_factors = zk_building_part::emission_factors_from_env()
+        .expect("Emission factor datasets could not be loaded!");
+    // DIDs of DPPs and their issuers, see Readme
+    let did_resolver: Arc<dyn DidResolver> = match env::var("DID_DOCUMENTS") {
+        Ok(path) => Arc::new(
+            StaticDidResolver::from_file(path).expect("DID documents could not be loaded!"),
+        ),
+        Err(_) => Arc::new(did_resolver()),
+    };
+    // only DPP credentials of these issuers are accepted, see Readme
+    let mut trusted_issuers: HashSet<Box<str>> = env::var("DPP_TRUSTED_ISSUERS")
+        .map(|issuers| {
+            issuers
+                .split(',')
+                .map(str::trim)
+                .filter(|issuer| !issuer.is_empty())
+                .map(Into::into)
+                .collect()
+        })
+        .unwrap_or_default();
+    let key_resolver: Arc<dyn KeyResolver> = match env::var("DPP_ISSUER_KEYS") {
+        Ok(path) => {
+            let keys =
+                StaticKeyResolver::from_file(path).expect("DPP issuer keys could not be loaded!");
+            if trusted_issuers.is_empty() {
+                trusted_issuers.extend(keys.issuers().map(Into::into));
+            }
+            Arc::new(keys)
+        }
+        Err(_) if env::var("DID_DOCUMENTS").is_ok() => Arc::new(did_resolver.clone()),
+        Err(_) => Arc::new(issuer_did_resolver()),
+    };
+    if trusted_issuers.is_empty() {
+        println!(
+            "No trusted DPP issuers configured, DPP credentials of other issuers are rejected!"
+        );
+    }
+    // key for results issued as verifiable credentials, see Readme
+    let issuer_key = env::var("ISSUER_KEY").ok().map(|secret_key| {
+        let verification_method = env::var("ISSUER_VERIFICATION_METHOD").ok();
+        IssuerKey::from_hex(verification_method.as_deref(), &secret_key)
+            .expect("Issuer key could not be loaded!")
+    });
+    if let Some(issuer_key) = &issuer_key {
+        println!("Issuing credentials as {}", issuer_key.issuer());
+    }
+    // asynchronous tasks survive a restart if they are stored in a database, see Readme
+    let task_store: Box<dyn TaskStore> = match env::var("TASK_DB") {
+        Ok(path) => {
+            Box::new(SqliteTaskStore::open(path).expect("Task database could not be opened!"))
+        }
+        Err(_) => Box::new(VolatileTaskStore),
+    };
+    let retention_hours = env_number("TASK_RETENTION_HOURS", DEFAULT_RETENTION_HOURS);
+    let defaults = ProvingOptions::default();
+    let options = ProvingOptions {
+        task_store,
+        retention: Some(Duration::from_secs(retention_hours * 60 * 60)),
+        max_finished: Some(env_number("TASK_MAX_FINISHED", DEFAULT_MAX_FINISHED_TASKS)),
+        admin_token: env::var("ADMIN_TOKEN").ok().map(Into::into),
+        max_concurrency: env_number("PROVING_WORKERS", defaults.max_concurrency),
+        queue_capacity: env_number("PROVING_QUEUE_SIZE", defaults.queue_capacity),
+        webhooks: WebhookOptions {
+            secret: env::var("WEBHOOK_SECRET")
+                .ok()
+                .map(|secret| secret.into_bytes().into()),
+            allowed_hosts: env::var("WEBHOOK_ALLOWED_HOSTS")
+                .map(|hosts| hosts.split(',').map(|host| host.trim().into()).collect())
+                .unwrap_or_default(),
+            ..defaults.webhooks
+        },
+        dev_mode: risc0_zkvm::is_dev_mode(),
+        proof_cache: env::var("PROOF_CACHE_DIR")
+            .ok()
+            .map(|dir| Arc::new(FsProofCache::new(dir)) as Arc<dyn ProofCache>),
+        proving_cycles_per_second: env_number(
+            "PROVING_CYCLES_PER_SECOND",
+            defaults.proving_cycles_per_second,
+        ),
+        groth16_proving_ms: env_number("GROTH16_PROVING_MS", defaults.groth16_proving_ms),
+    };
+    if options.dev_mode {
+        println!("Dev mode: guests are only executed, the receipts are fake!");
+    }
+    let (app, handle) = start_prover_service(
+        zk_building_part::builder(emission_factors),
+        key_resolver,
+        trusted_issuers,
+        did_resolver,
+        issuer_key,
+        options,
+    );
 
     let listener = tokio::net::TcpListener::bind(server_address).await.unwrap();
     axum::serve(listener, app)
@@ -29,6 +133,27 @@ async fn main() {
         .expect("Proving Service Background Task did not terminate correctly!");
 }
 
+fn env_number<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name).map_or(default, |value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} must be a number!"))
+    })
+}
+
+fn did_resolver() -> DidResolverRegistry {
+    issuer_did_resolver().with_method("key", Arc::new(DidKey))
+}
+
+/// Resolves the keys of DPP issuers. Anyone can create a `did:key`, so its key is only used if it
+/// is configured in `DPP_ISSUER_KEYS`.
+fn issuer_did_resolver() -> DidResolverRegistry {
+    let oydid_url = env::var("OYDID_URL").unwrap_or(DidOyd::DEFAULT_BASE_URL.into());
+    DidResolverRegistry::default()
+        .with_method("web", Arc::new(DidWeb::default()))
+        .with_method("oyd", Arc::new(DidOyd::new(&oydid_url)))
+}
+
 // From: https://github.com/tokio-rs/axum/blob/main/examples/graceful-shutdown/src/main.rs
 // This function creates a future that waits for a SIGINT (Ctrl+C) signal.
 async fn shutdown_signal() {
@@ -3,30 +3,78 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use risc0_zkvm::guest::env;
-use zk_building_part_guest::{BuildingDefinition, BuildingEpd};
+use risc0_zkvm::guest::{abort, env};
+use risc0_zkvm::serde::to_vec;
+use zk_building_part_guest::{
+    factors_digests, BuildingDefinition, BuildingEpd, Indicators, LifeCycleModules,
+};
+use zk_epdcalc_core::{commit_input, ImageId, Salt, AGGREGATE_OVERFLOW};
 
 fn main() {
+    let building_part_image_id: ImageId = env::read();
+    let concrete_image_id: ImageId = env::read();
+    let salt: Salt = env::read();
+    let definition: BuildingDefinition = env::read();
+    let input_commitment = commit_input(&salt, &definition).expect("Input could not be committed!");
+
     let BuildingDefinition {
         date,
         building,
         building_parts,
         site_energy,
-    } = env::read();
+        ..
+    } = definition;
+
+    for bp_epd in &building_parts {
+        // the building part receipts are added as assumptions by the host
+        let journal = to_vec(&**bp_epd).expect("Building part EPD could not be serialized!");
+        env::verify(building_part_image_id, &journal)
+            .expect("Building part EPD could not be verified!");
 
-    let a14_gwp: u32 = building_parts.iter().map(|bp_epd| bp_epd.a14_gwp).sum();
+        if bp_epd.concrete_image_id != concrete_image_id {
+            abort("Building part EPD is based on an unknown concrete guest!")
+        }
+    }
 
-    let a5_gwp: u32 = site_energy
+    let parts_impacts =
+        Indicators::checked_sum(building_parts.iter().map(|bp_epd| &bp_epd.impacts))
+            .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: impacts of building parts")));
+
+    let energy_impacts: Vec<Indicators<LifeCycleModules>> = site_energy
         .iter()
-        .map(|energy| energy.gwp * energy.amount)
-        .sum();
+        .map(|energy| {
+            energy
+                .impacts
+                .map(|value| {
+                    value
+                        .checked_mul_int(energy.amount.into())
+                        .map(|value| LifeCycleModules::single(energy.module, value))
+                })
+                .transpose()
+                .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: impacts of site energy")))
+        })
+        .collect();
+    let energy_impacts = Indicators::checked_sum(&energy_impacts)
+        .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: impacts of site energy")));
+
+    let factors_digests = factors_digests(
+        building_parts
+            .iter()
+            .flat_map(|bp_epd| &bp_epd.factors_digests),
+    );
 
-    let a15_gwp = a14_gwp + a5_gwp;
+    let impacts = parts_impacts
+        .checked_add(&energy_impacts)
+        .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: impacts of building")));
 
     let epd = BuildingEpd {
         date,
         building,
-        a15_gwp,
+        impacts,
+        building_part_image_id,
+        concrete_image_id,
+        factors_digests,
+        input_commitment,
     };
 
     env::commit(&epd)
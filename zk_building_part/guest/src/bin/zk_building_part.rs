@@ -3,21 +3,53 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use risc0_zkvm::guest::env;
-use zk_building_part_guest::{BuildingPartDefinition, BuildingPartEPD};
+use risc0_zkvm::guest::{abort, env};
+use risc0_zkvm::serde::to_vec;
+use zk_building_part_guest::{
+    factors_digests, BuildingPartDefinition, BuildingPartEPD, Indicators, LifeCycleModules,
+};
+use zk_epdcalc_core::{commit_input, ImageId, Salt, AGGREGATE_OVERFLOW};
 
 fn main() {
-    // read the input
+    // read the image id of the concrete guest and the input
+    let concrete_image_id: ImageId = env::read();
+    let salt: Salt = env::read();
     let definition: BuildingPartDefinition = env::read();
+    let input_commitment = commit_input(&salt, &definition).expect("Input could not be committed!");
 
-    let a14_gwp= definition.used_material.iter().map(|m| {m.a13_gwp * m.amount }).sum();
+    let material_impacts: Vec<Indicators<LifeCycleModules>> = definition
+        .used_material
+        .iter()
+        .map(|m| {
+            // the concrete receipts are added as assumptions by the host
+            let journal = to_vec(&m.epd).expect("Concrete EPD could not be serialized!");
+            env::verify(concrete_image_id, &journal).expect("Concrete EPD could not be verified!");
 
-    let epd = BuildingPartEPD{
+            m.epd
+                .impacts
+                .checked_mul_int(m.amount.into())
+                .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: impacts of material use")))
+        })
+        .collect();
+    let impacts = Indicators::checked_sum(&material_impacts)
+        .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: impacts of building part")));
+    // the verifier checks that the factors are a registered dataset
+    let factors_digests = factors_digests(
+        definition
+            .used_material
+            .iter()
+            .map(|m| &m.epd.factors_digest),
+    );
+
+    let epd = BuildingPartEPD {
         date: definition.date,
         building: definition.building,
         building_part_id: definition.building_part_id,
-        a14_gwp,
+        impacts,
+        concrete_image_id,
+        factors_digests,
+        input_commitment,
     };
     // write public output to the journal
     env::commit(&epd);
-}
\ No newline at end of file
+}
@@ -4,38 +4,52 @@
  */
 
 use risc0_zkvm::guest::{abort, env};
-use zk_building_part_guest::{ConcreteEpd, ConcreteMixture};
+use zk_building_part_guest::{ConcreteEpd, ConcreteMixture, EmissionFactors, LifeCycleModules};
+use zk_epdcalc_core::{commit_input, digest_parameters, Decimal, Salt};
 
 fn main() {
     // read the input
+    let salt: Salt = env::read();
+    let factors: EmissionFactors = env::read();
     let mixture: ConcreteMixture = env::read();
-    let factory_wien_gwp = 3.9f64;
-    let factory_eggendorf_gwp = 3.6f64;
-    let cement_gwp = 1.20f64;
-    let additive_gwp = 0.15f64;
-    let gravel_gwp = 0.009f64;
-    let water_gwp = 0.005f64;
-
-    let factory_gwp = if mixture.factory == "Wien" {
-        factory_wien_gwp
-    } else if mixture.factory == "Eggendorf" {
-        factory_eggendorf_gwp
-    } else { abort("Unknown factory!") };
-
-    if mixture.cement + mixture.water + mixture.additives + mixture.gravel != 1000.0 {
+    let input_commitment = commit_input(&salt, &mixture).expect("Input could not be committed!");
+    let factors_digest = digest_parameters(&factors).expect("Factors could not be hashed!");
+
+    let factory_impacts = factors
+        .factories
+        .iter()
+        .find(|factory| factory.factory == mixture.factory)
+        .map(|factory| factory.impacts)
+        .unwrap_or_else(|| abort("Unknown factory!"));
+
+    // a negative amount would offset the impacts of the other components
+    let components = [mixture.cement, mixture.water, mixture.additives, mixture.gravel];
+    if components.iter().any(|&amount| amount < Decimal::ZERO) {
+        abort("Concrete mixture components must not be negative!")
+    }
+
+    let total = mixture.cement + mixture.water + mixture.additives + mixture.gravel;
+    if total != Decimal::from_int(1000) {
         abort("Concrete mixture does not sum up to 1000!")
     }
 
-    let gwp = mixture.cement * cement_gwp +
-        mixture.gravel * gravel_gwp +
-        mixture.water * water_gwp +
-        mixture.additives * additive_gwp +
-        factory_gwp;
+    let a1_a3_impacts = factors.cement * mixture.cement +
+        factors.gravel * mixture.gravel +
+        factors.water * mixture.water +
+        factors.additives * mixture.additives +
+        factory_impacts;
+
+    let impacts = factors.modules.zip_with(&a1_a3_impacts, |&modules, &a1_a3| LifeCycleModules {
+        a1_a3: modules.a1_a3 + a1_a3,
+        ..modules
+    });
 
     let epd = ConcreteEpd {
         description: mixture.description,
         factory: mixture.factory,
-        a13_gwp: gwp,
+        impacts,
+        input_commitment,
+        factors_digest,
     };
     // write public output to the journal
     env::commit(&epd);
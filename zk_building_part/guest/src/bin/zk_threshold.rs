@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use risc0_zkvm::guest::{abort, env};
+use risc0_zkvm::serde::to_vec;
+use zk_building_part_guest::{ThresholdDefinition, ThresholdEpd};
+use zk_epdcalc_core::{commit_input, Decimal, ImageId, Salt, AGGREGATE_OVERFLOW};
+
+fn main() {
+    let building_image_id: ImageId = env::read();
+    let building_part_image_id: ImageId = env::read();
+    let concrete_image_id: ImageId = env::read();
+    let salt: Salt = env::read();
+    let definition: ThresholdDefinition = env::read();
+    let input_commitment = commit_input(&salt, &definition).expect("Input could not be committed!");
+
+    let ThresholdDefinition {
+        building_epd,
+        reference_area,
+        gwp_limit,
+        ..
+    } = definition;
+
+    // the building receipt is added as assumption by the host
+    let journal = to_vec(&building_epd).expect("Building EPD could not be serialized!");
+    env::verify(building_image_id, &journal).expect("Building EPD could not be verified!");
+
+    if building_epd.building_part_image_id != building_part_image_id
+        || building_epd.concrete_image_id != concrete_image_id
+    {
+        abort("Building EPD is based on an unknown guest!")
+    }
+    if reference_area <= Decimal::ZERO {
+        abort("Reference area must be positive!")
+    }
+
+    let gwp = building_epd
+        .impacts
+        .gwp
+        .checked_total()
+        .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: GWP of building")));
+    let limit = gwp_limit
+        .checked_mul(reference_area)
+        .unwrap_or_else(|| abort(&format!("{AGGREGATE_OVERFLOW}: GWP limit of building")));
+
+    // only the limit and the result are public, the GWP of the building stays private
+    let epd = ThresholdEpd {
+        date: building_epd.date,
+        building: building_epd.building,
+        reference_area,
+        gwp_limit,
+        within_limit: gwp <= limit,
+        building_image_id,
+        building_part_image_id,
+        concrete_image_id,
+        factors_digests: building_epd.factors_digests,
+        input_commitment,
+    };
+
+    env::commit(&epd)
+}
@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use crate::LifeCycleModules;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, Mul};
+use zk_epdcalc_core::Decimal;
+
+/// Environmental impact indicators of an EPD (EN 15804).
+///
+/// `T` is the value of a single indicator, e.g. a [`Decimal`] factor or the [`LifeCycleModules`]
+/// breakdown of an EPD. Missing indicators are zero when deserialized from JSON, so a DPP that
+/// only states `gwp` is still accepted.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(default, bound(deserialize = "T: Deserialize<'de> + Default"))]
+pub struct Indicators<T> {
+    /// Global warming potential
+    pub gwp: T,
+    /// Ozone depletion potential
+    pub odp: T,
+    /// Acidification potential
+    pub ap: T,
+    /// Eutrophication potential
+    pub ep: T,
+    /// Photochemical ozone creation potential
+    pub pocp: T,
+    /// Abiotic depletion potential
+    pub adp: T,
+    /// Use of non-renewable primary energy
+    pub penre: T,
+    /// Use of renewable primary energy
+    pub pere: T,
+}
+
+impl<T> Indicators<T> {
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> Indicators<U> {
+        Indicators {
+            gwp: f(&self.gwp),
+            odp: f(&self.odp),
+            ap: f(&self.ap),
+            ep: f(&self.ep),
+            pocp: f(&self.pocp),
+            adp: f(&self.adp),
+            penre: f(&self.penre),
+            pere: f(&self.pere),
+        }
+    }
+
+    pub fn zip_with<U, V>(
+        &self,
+        other: &Indicators<U>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> Indicators<V> {
+        Indicators {
+            gwp: f(&self.gwp, &other.gwp),
+            odp: f(&self.odp, &other.odp),
+            ap: f(&self.ap, &other.ap),
+            ep: f(&self.ep, &other.ep),
+            pocp: f(&self.pocp, &other.pocp),
+            adp: f(&self.adp, &other.adp),
+            penre: f(&self.penre, &other.penre),
+            pere: f(&self.pere, &other.pere),
+        }
+    }
+}
+
+impl<T> Indicators<Option<T>> {
+    /// `None` if any of the indicators is `None`.
+    pub fn transpose(self) -> Option<Indicators<T>> {
+        Some(Indicators {
+            gwp: self.gwp?,
+            odp: self.odp?,
+            ap: self.ap?,
+            ep: self.ep?,
+            pocp: self.pocp?,
+            adp: self.adp?,
+            penre: self.penre?,
+            pere: self.pere?,
+        })
+    }
+}
+
+impl Indicators<LifeCycleModules> {
+    /// Indicator and module wise sum, `None` on overflow.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        self.zip_with(other, LifeCycleModules::checked_add)
+            .transpose()
+    }
+
+    /// Indicator and module wise multiplication with an amount, `None` on overflow.
+    pub fn checked_mul_int(&self, amount: i64) -> Option<Self> {
+        self.map(|modules| modules.checked_mul_int(amount))
+            .transpose()
+    }
+
+    pub fn checked_sum<'a>(iter: impl IntoIterator<Item = &'a Self>) -> Option<Self> {
+        iter.into_iter()
+            .try_fold(Self::default(), |sum, impacts| sum.checked_add(impacts))
+    }
+}
+
+impl Add for Indicators<Decimal> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, |&a, &b| a + b)
+    }
+}
+
+/// Scales all indicators by an amount, e.g. the quantity of a material.
+impl Mul<Decimal> for Indicators<Decimal> {
+    type Output = Self;
+
+    fn mul(self, rhs: Decimal) -> Self::Output {
+        self.map(|&value| value * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Module;
+
+    fn indicators(value: i64) -> Indicators<Decimal> {
+        Indicators {
+            gwp: Decimal::from_int(value),
+            odp: Decimal::from_int(value + 1),
+            ap: Decimal::from_int(value + 2),
+            ep: Decimal::from_int(value + 3),
+            pocp: Decimal::from_int(value + 4),
+            adp: Decimal::from_int(value + 5),
+            penre: Decimal::from_int(value + 6),
+            pere: Decimal::from_int(value + 7),
+        }
+    }
+
+    #[test]
+    fn zip_indicator_wise() {
+        let zipped = indicators(0).zip_with(&indicators(10), |a, b| (*a, *b));
+        assert_eq!(zipped.gwp, (Decimal::from_int(0), Decimal::from_int(10)));
+        assert_eq!(zipped.pocp, (Decimal::from_int(4), Decimal::from_int(14)));
+        assert_eq!(zipped.pere, (Decimal::from_int(7), Decimal::from_int(17)));
+        assert_eq!(
+            indicators(0) + indicators(10),
+            indicators(0).zip_with(&indicators(10), |&a, &b| a + b)
+        );
+        assert_eq!((indicators(1) + indicators(2)).adp, Decimal::from_int(13));
+    }
+
+    #[test]
+    fn transpose_fails_on_any_missing_indicator() {
+        let some = indicators(0).map(|&value| Some(value));
+        assert_eq!(some.transpose(), Some(indicators(0)));
+        let mut missing = some;
+        missing.pere = None;
+        assert_eq!(missing.transpose(), None);
+        let mut missing = some;
+        missing.gwp = None;
+        assert_eq!(missing.transpose(), None);
+    }
+
+    #[test]
+    fn checked_arithmetic_detects_overflow() {
+        let impacts = indicators(1).map(|&value| LifeCycleModules::single(Module::A1A3, value));
+        let sum = Indicators::checked_sum(&[impacts, impacts.checked_mul_int(2).unwrap()]).unwrap();
+        assert_eq!(sum.gwp.a1_a3, Decimal::from_int(3));
+        assert_eq!(sum.pere.a1_a3, Decimal::from_int(24));
+
+        // an overflow of a single module of a single indicator fails the whole operation
+        let mut large = Indicators::<LifeCycleModules>::default();
+        large.penre.d = Decimal::from_units(i64::MAX);
+        assert_eq!(large.checked_mul_int(2), None);
+        let one = Indicators {
+            penre: LifeCycleModules::single(Module::D, Decimal::from_units(1)),
+            ..Default::default()
+        };
+        assert_eq!(large.checked_add(&one), None);
+        assert_eq!(Indicators::checked_sum(&[large, one]), None);
+        assert_eq!(
+            Indicators::checked_sum(&[large, impacts]).map(|sum| sum.penre.d),
+            Some(large.penre.d)
+        );
+    }
+}
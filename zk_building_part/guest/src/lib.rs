@@ -7,12 +7,19 @@
 use anyhow::{anyhow, Result};
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 use itertools::Itertools;
+use risc0_zkvm::sha::Digest;
 use serde::{Deserialize, Serialize};
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
-use zk_epdcalc_core::VerifiedEpd;
+use zk_epdcalc_core::{CommittedEpd, ComposedEpd, ComposedInput, Decimal, ImageId, VerifiedEpd};
+
+pub use indicators::Indicators;
+pub use modules::{LifeCycleModules, Module};
+
+mod indicators;
+mod modules;
 
 #[derive(Deserialize, Serialize, Debug)]
 #[cfg_attr(
@@ -24,6 +31,20 @@ pub struct BuildingPartDefinition {
     pub building: Box<str>,
     pub building_part_id: Box<str>,
     pub used_material: Vec<MaterialUse>,
+    /// Proven concrete EPDs, added as assumptions for the material uses.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[serde(skip)]
+    pub concrete_epds: Vec<ZkConcreteEpd>,
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+impl ComposedInput for BuildingPartDefinition {
+    fn assumptions(&self) -> Vec<&str> {
+        self.concrete_epds
+            .iter()
+            .map(|zk_epd| zk_epd.get_zkp())
+            .collect()
+    }
 }
 
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
@@ -32,24 +53,23 @@ impl TryFrom<CreateBuildingPartRequestDTO> for BuildingPartDefinition {
 
     fn try_from(request: CreateBuildingPartRequestDTO) -> Result<Self, Self::Error> {
         let used_material_dto = &request.definition.used_material;
-        let used_material: Result<Vec<MaterialUse>> = used_material_dto
-            .iter()
-            .map(|material_use: &MaterialUseDto| {
-                let amount = material_use.amount;
-                let did = &*material_use.concrete_dpp_did;
-                let mapping = request.get_did_mapping(did)?;
-                let vc = mapping.get_vc()?;
-                let a13_gwp = vc.credential_subject.epd.epd.a13_gwp;
-                if a13_gwp > u32::MAX as f64 || a13_gwp < 0f64 {
-                    Err(anyhow!("a13_gwp out of range: {a13_gwp}"))?;
-                }
-                let a13_gwp: u32 = a13_gwp as u32;
-                Ok(MaterialUse { amount, a13_gwp }) as Result<MaterialUse, Self::Error>
-            })
-            .collect();
+        let (used_material, concrete_epds): (Vec<MaterialUse>, Vec<ZkConcreteEpd>) =
+            used_material_dto
+                .iter()
+                .map(|material_use: &MaterialUseDto| {
+                    let amount = material_use.amount;
+                    let did = &*material_use.concrete_dpp_did;
+                    let mapping = request.get_did_mapping(did)?;
+                    let vc = mapping.get_vc()?;
+                    let zk_epd = vc.credential_subject.epd.clone();
+                    let epd = zk_epd.epd.clone();
+                    Ok((MaterialUse { amount, epd }, zk_epd)) as Result<_, Self::Error>
+                })
+                .process_results(|iter| iter.unzip())?;
 
         Ok(Self {
-            used_material: used_material?,
+            used_material,
+            concrete_epds,
             date: request.definition.date,
             building: request.definition.building,
             building_part_id: request.definition.building_part_id,
@@ -60,21 +80,45 @@ impl TryFrom<CreateBuildingPartRequestDTO> for BuildingPartDefinition {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct MaterialUse {
     pub amount: u32,
-    pub a13_gwp: u32,
+    pub epd: ConcreteEpd,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ConcreteMixture {
     pub description: String,
-    pub cement: f64,
-    pub gravel: f64,
-    pub water: f64,
-    pub additives: f64,
+    pub cement: Decimal,
+    pub gravel: Decimal,
+    pub water: Decimal,
+    pub additives: Decimal,
     pub material: String,
     pub factory: String,
 }
 
+impl ComposedInput for ConcreteMixture {}
+
+/// Impact factors used by the concrete guest.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct EmissionFactors {
+    pub cement: Indicators<Decimal>,
+    pub gravel: Indicators<Decimal>,
+    pub water: Indicators<Decimal>,
+    pub additives: Indicators<Decimal>,
+    pub factories: Vec<FactoryEmission>,
+    /// Impacts per unit of concrete in the modules after the product stage (e.g. transport and
+    /// end of life scenarios). The calculated A1-A3 values are added to `A1_A3`.
+    #[serde(default)]
+    pub modules: Indicators<LifeCycleModules>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FactoryEmission {
+    pub factory: String,
+    pub impacts: Indicators<Decimal>,
+}
+
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 #[derive(Deserialize, Debug)]
 pub struct CreateBuildingPartRequestDTO {
@@ -147,28 +191,42 @@ pub struct MaterialUseDto {
 pub struct ConcreteMixtureDppDto {
     pub id: String,
     pub epd: ZkConcreteEpd,
-    pub date: String,
-    pub volume: u32,
+    /// Not stated in the credentials issued by the service.
+    pub date: Option<String>,
+    /// Not stated in the credentials issued by the service.
+    pub volume: Option<u32>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ConcreteEpd {
     pub description: String,
     pub factory: String,
-    #[serde(rename = "A13_gwp")]
-    pub a13_gwp: f64,
+    pub impacts: Indicators<LifeCycleModules>,
+    pub input_commitment: Digest,
+    pub factors_digest: Digest,
 }
 
-impl PartialEq for ConcreteEpd {
-    fn eq(&self, other: &Self) -> bool {
-        self.a13_gwp == other.a13_gwp
-            && self.factory == other.factory
-            && self.description == other.description
+impl ComposedEpd for ConcreteEpd {
+    fn parameter_digests(&self) -> Vec<Digest> {
+        vec![self.factors_digest]
     }
 }
 
-impl Eq for ConcreteEpd {}
+/// Sorted digests of the emission factor datasets without duplicates, as committed by the guests
+/// that are based on concrete EPDs.
+pub fn factors_digests<'a>(digests: impl IntoIterator<Item = &'a Digest>) -> Vec<Digest> {
+    let mut digests: Vec<Digest> = digests.into_iter().copied().collect();
+    digests.sort_unstable();
+    digests.dedup();
+    digests
+}
+
+impl CommittedEpd for ConcreteEpd {
+    fn input_commitment(&self) -> &Digest {
+        &self.input_commitment
+    }
+}
 
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 #[derive(Deserialize, Serialize, Clone)]
@@ -187,6 +245,8 @@ impl Debug for ZkConcreteEpd {
 
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 impl VerifiedEpd<ConcreteEpd> for ZkConcreteEpd {
+    const SUBJECT_MEMBER: Option<&'static str> = Some("epd");
+
     fn get_zkp(&self) -> &str {
         &self.zkp
     }
@@ -205,10 +265,30 @@ pub struct BuildingPartEPD {
     pub date: Box<str>,
     pub building: Box<str>,
     pub building_part_id: Box<str>,
-    pub a14_gwp: u32,
+    pub impacts: Indicators<LifeCycleModules>,
+    pub concrete_image_id: ImageId,
+    /// Digests of the emission factor datasets of the used concrete.
+    pub factors_digests: Vec<Digest>,
+    pub input_commitment: Digest,
+}
+
+impl ComposedEpd for BuildingPartEPD {
+    fn assumption_image_ids(&self) -> Vec<ImageId> {
+        vec![self.concrete_image_id]
+    }
+
+    fn parameter_digests(&self) -> Vec<Digest> {
+        self.factors_digests.clone()
+    }
+}
+
+impl CommittedEpd for BuildingPartEPD {
+    fn input_commitment(&self) -> &Digest {
+        &self.input_commitment
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ZkBuildingPartEPD {
     #[serde(flatten)]
     pub epd: Arc<BuildingPartEPD>,
@@ -241,7 +321,9 @@ impl VerifiedEpd<BuildingPartEPD> for ZkBuildingPartEPD {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ConstructionSiteEnergy {
     pub amount: u32,
-    pub gwp: u32,
+    pub impacts: Indicators<Decimal>,
+    /// Life cycle module the energy is accounted to, e.g. A5 for construction or B6 for operation.
+    pub module: Module,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -254,6 +336,20 @@ pub struct BuildingDefinition {
     pub building: Box<str>,
     pub building_parts: Vec<Arc<BuildingPartEPD>>,
     pub site_energy: Vec<ConstructionSiteEnergy>,
+    /// Proven building part EPDs, added as assumptions for the building parts.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[serde(skip)]
+    pub building_part_epds: Vec<ZkBuildingPartEPD>,
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+impl ComposedInput for BuildingDefinition {
+    fn assumptions(&self) -> Vec<&str> {
+        self.building_part_epds
+            .iter()
+            .map(|zk_epd| zk_epd.get_zkp())
+            .collect()
+    }
 }
 
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
@@ -264,17 +360,17 @@ impl TryFrom<CreateBuildingDefinitionDto> for BuildingDefinition {
         let date = request.definition.date;
         let building = request.definition.building;
 
-        let building_parts: HashMap<&str, Arc<BuildingPartEPD>> = request
+        let building_parts: HashMap<&str, &ZkBuildingPartEPD> = request
             .building_part_dpps
             .iter()
             .map(|mapping| {
                 mapping
                     .get_vc()
-                    .map(|vc| (mapping.did.as_str(), vc.credential_subject.epd.clone()))
+                    .map(|vc| (mapping.did.as_str(), &vc.credential_subject))
             })
             .try_collect()?;
 
-        let building_parts = request
+        let building_part_epds: Vec<ZkBuildingPartEPD> = request
             .definition
             .building_part_dids
             .iter()
@@ -284,7 +380,7 @@ impl TryFrom<CreateBuildingDefinitionDto> for BuildingDefinition {
                  }| {
                     building_parts
                         .get(&**building_part_dpp_did)
-                        .cloned()
+                        .map(|&zk_epd| zk_epd.clone())
                         .ok_or(anyhow!(
                             "No Dpp found for building part {building_part_dpp_did}"
                         ))
@@ -292,13 +388,18 @@ impl TryFrom<CreateBuildingDefinitionDto> for BuildingDefinition {
             )
             .try_collect()?;
 
-        let energy_gwp_map: HashMap<&str, u32> = request
+        let building_parts = building_part_epds
+            .iter()
+            .map(|zk_epd| zk_epd.epd.clone())
+            .collect();
+
+        let energy_impacts_map: HashMap<&str, Indicators<Decimal>> = request
             .site_energy_dpps
             .iter()
             .map(|mapping| {
                 mapping
                     .get_vc()
-                    .map(|vc| (mapping.did.as_str(), vc.credential_subject.gwp))
+                    .map(|vc| (mapping.did.as_str(), vc.credential_subject.impacts))
             })
             .try_collect()?;
 
@@ -306,15 +407,22 @@ impl TryFrom<CreateBuildingDefinitionDto> for BuildingDefinition {
             .definition
             .site_energy_dids
             .iter()
-            .map(|ConstructionSiteEnergyDto { amount, dpp_did }| {
-                energy_gwp_map
-                    .get(dpp_did.as_ref())
-                    .map(|&gwp| ConstructionSiteEnergy {
-                        gwp,
-                        amount: *amount,
-                    })
-                    .ok_or(anyhow!("No Dpp found for SiteEnergy {dpp_did}"))
-            })
+            .map(
+                |ConstructionSiteEnergyDto {
+                     amount,
+                     dpp_did,
+                     module,
+                 }| {
+                    energy_impacts_map
+                        .get(dpp_did.as_ref())
+                        .map(|&impacts| ConstructionSiteEnergy {
+                            impacts,
+                            amount: *amount,
+                            module: *module,
+                        })
+                        .ok_or(anyhow!("No Dpp found for SiteEnergy {dpp_did}"))
+                },
+            )
             .try_collect()?;
 
         Ok(BuildingDefinition {
@@ -322,6 +430,7 @@ impl TryFrom<CreateBuildingDefinitionDto> for BuildingDefinition {
             building,
             building_parts,
             site_energy,
+            building_part_epds,
         })
     }
 }
@@ -360,19 +469,48 @@ struct ConstructionSiteEnergyDto {
     amount: u32,
     #[serde(rename = "dppDid")]
     dpp_did: Box<str>,
+    #[serde(default = "construction_module")]
+    module: Module,
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn construction_module() -> Module {
+    Module::A5
 }
 
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 #[derive(Deserialize)]
 struct EnergyDpp {
-    gwp: u32,
+    #[serde(flatten)]
+    impacts: Indicators<Decimal>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Eq, PartialEq)]
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
 pub struct BuildingEpd {
     pub date: Box<str>,
     pub building: Box<str>,
-    pub a15_gwp: u32,
+    pub impacts: Indicators<LifeCycleModules>,
+    pub building_part_image_id: ImageId,
+    pub concrete_image_id: ImageId,
+    /// Digests of the emission factor datasets of the concrete in all building parts.
+    pub factors_digests: Vec<Digest>,
+    pub input_commitment: Digest,
+}
+
+impl ComposedEpd for BuildingEpd {
+    fn assumption_image_ids(&self) -> Vec<ImageId> {
+        vec![self.building_part_image_id, self.concrete_image_id]
+    }
+
+    fn parameter_digests(&self) -> Vec<Digest> {
+        self.factors_digests.clone()
+    }
+}
+
+impl CommittedEpd for BuildingEpd {
+    fn input_commitment(&self) -> &Digest {
+        &self.input_commitment
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -396,6 +534,112 @@ impl VerifiedEpd<BuildingEpd> for ZkBuildingEpd {
     }
 }
 
+/// Input of the threshold guest, a building EPD and the GWP limit it is checked against.
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    serde(from = "CreateThresholdDefinitionDto")
+)]
+pub struct ThresholdDefinition {
+    pub building_epd: BuildingEpd,
+    /// Reference floor area of the building in m².
+    pub reference_area: Decimal,
+    /// Limit of the GWP (modules A1 to C4) per m² of reference area.
+    pub gwp_limit: Decimal,
+    /// Proof of the building EPD, added as assumption.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[serde(skip)]
+    pub building_epd_zkp: Box<str>,
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+impl ComposedInput for ThresholdDefinition {
+    fn assumptions(&self) -> Vec<&str> {
+        vec![&self.building_epd_zkp]
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+impl From<CreateThresholdDefinitionDto> for ThresholdDefinition {
+    fn from(request: CreateThresholdDefinitionDto) -> Self {
+        Self {
+            building_epd: request.building_epd.epd,
+            reference_area: request.reference_area,
+            gwp_limit: request.gwp_limit,
+            building_epd_zkp: request.building_epd.zkp,
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[derive(Deserialize)]
+struct CreateThresholdDefinitionDto {
+    #[serde(rename = "buildingEpd")]
+    building_epd: ZkBuildingEpd,
+    #[serde(rename = "referenceArea")]
+    reference_area: Decimal,
+    #[serde(rename = "gwpLimit")]
+    gwp_limit: Decimal,
+}
+
+/// Public output of the threshold guest, states whether a building stays within a GWP limit
+/// without revealing its impacts.
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq)]
+pub struct ThresholdEpd {
+    pub date: Box<str>,
+    pub building: Box<str>,
+    pub reference_area: Decimal,
+    pub gwp_limit: Decimal,
+    pub within_limit: bool,
+    pub building_image_id: ImageId,
+    pub building_part_image_id: ImageId,
+    pub concrete_image_id: ImageId,
+    /// Digests of the emission factor datasets of the building.
+    pub factors_digests: Vec<Digest>,
+    pub input_commitment: Digest,
+}
+
+impl ComposedEpd for ThresholdEpd {
+    fn assumption_image_ids(&self) -> Vec<ImageId> {
+        vec![
+            self.building_image_id,
+            self.building_part_image_id,
+            self.concrete_image_id,
+        ]
+    }
+
+    fn parameter_digests(&self) -> Vec<Digest> {
+        self.factors_digests.clone()
+    }
+}
+
+impl CommittedEpd for ThresholdEpd {
+    fn input_commitment(&self) -> &Digest {
+        &self.input_commitment
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ZkThresholdEpd {
+    #[serde(flatten)]
+    epd: ThresholdEpd,
+    zkp: Box<str>,
+}
+
+impl VerifiedEpd<ThresholdEpd> for ZkThresholdEpd {
+    fn get_zkp(&self) -> &str {
+        &self.zkp
+    }
+
+    fn get_epd(&self) -> &ThresholdEpd {
+        &self.epd
+    }
+
+    fn from_result(epd: ThresholdEpd, zkp: Box<str>) -> Self {
+        Self { epd, zkp }
+    }
+}
+
 /*#[cfg(test)]
 mod test {
     use std::fs;
@@ -0,0 +1,239 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use serde::{Deserialize, Serialize};
+use zk_epdcalc_core::Decimal;
+
+/// Life cycle modules of an EPD according to EN 15804.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Module {
+    #[serde(rename = "A1_A3")]
+    A1A3,
+    A4,
+    A5,
+    B1,
+    B2,
+    B3,
+    B4,
+    B5,
+    B6,
+    B7,
+    C1,
+    C2,
+    C3,
+    C4,
+    D,
+}
+
+impl Module {
+    pub const ALL: [Module; 15] = [
+        Module::A1A3,
+        Module::A4,
+        Module::A5,
+        Module::B1,
+        Module::B2,
+        Module::B3,
+        Module::B4,
+        Module::B5,
+        Module::B6,
+        Module::B7,
+        Module::C1,
+        Module::C2,
+        Module::C3,
+        Module::C4,
+        Module::D,
+    ];
+}
+
+/// Value of an impact indicator per life cycle module.
+///
+/// Missing modules are zero when deserialized from JSON.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LifeCycleModules {
+    #[serde(rename = "A1_A3")]
+    pub a1_a3: Decimal,
+    #[serde(rename = "A4")]
+    pub a4: Decimal,
+    #[serde(rename = "A5")]
+    pub a5: Decimal,
+    #[serde(rename = "B1")]
+    pub b1: Decimal,
+    #[serde(rename = "B2")]
+    pub b2: Decimal,
+    #[serde(rename = "B3")]
+    pub b3: Decimal,
+    #[serde(rename = "B4")]
+    pub b4: Decimal,
+    #[serde(rename = "B5")]
+    pub b5: Decimal,
+    #[serde(rename = "B6")]
+    pub b6: Decimal,
+    #[serde(rename = "B7")]
+    pub b7: Decimal,
+    #[serde(rename = "C1")]
+    pub c1: Decimal,
+    #[serde(rename = "C2")]
+    pub c2: Decimal,
+    #[serde(rename = "C3")]
+    pub c3: Decimal,
+    #[serde(rename = "C4")]
+    pub c4: Decimal,
+    #[serde(rename = "D")]
+    pub d: Decimal,
+}
+
+impl LifeCycleModules {
+    /// Breakdown with `value` in `module` and zero in all other modules.
+    pub fn single(module: Module, value: Decimal) -> Self {
+        let mut modules = Self::default();
+        *modules.get_mut(module) = value;
+        modules
+    }
+
+    pub fn get(&self, module: Module) -> Decimal {
+        match module {
+            Module::A1A3 => self.a1_a3,
+            Module::A4 => self.a4,
+            Module::A5 => self.a5,
+            Module::B1 => self.b1,
+            Module::B2 => self.b2,
+            Module::B3 => self.b3,
+            Module::B4 => self.b4,
+            Module::B5 => self.b5,
+            Module::B6 => self.b6,
+            Module::B7 => self.b7,
+            Module::C1 => self.c1,
+            Module::C2 => self.c2,
+            Module::C3 => self.c3,
+            Module::C4 => self.c4,
+            Module::D => self.d,
+        }
+    }
+
+    pub fn get_mut(&mut self, module: Module) -> &mut Decimal {
+        match module {
+            Module::A1A3 => &mut self.a1_a3,
+            Module::A4 => &mut self.a4,
+            Module::A5 => &mut self.a5,
+            Module::B1 => &mut self.b1,
+            Module::B2 => &mut self.b2,
+            Module::B3 => &mut self.b3,
+            Module::B4 => &mut self.b4,
+            Module::B5 => &mut self.b5,
+            Module::B6 => &mut self.b6,
+            Module::B7 => &mut self.b7,
+            Module::C1 => &mut self.c1,
+            Module::C2 => &mut self.c2,
+            Module::C3 => &mut self.c3,
+            Module::C4 => &mut self.c4,
+            Module::D => &mut self.d,
+        }
+    }
+
+    /// Module wise sum, `None` on overflow.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut sum = *self;
+        for module in Module::ALL {
+            *sum.get_mut(module) = self.get(module).checked_add(other.get(module))?;
+        }
+        Some(sum)
+    }
+
+    /// Module wise multiplication with an amount, `None` on overflow.
+    pub fn checked_mul_int(&self, amount: i64) -> Option<Self> {
+        let mut product = *self;
+        for module in Module::ALL {
+            *product.get_mut(module) = self.get(module).checked_mul_int(amount)?;
+        }
+        Some(product)
+    }
+
+    pub fn checked_sum<'a>(iter: impl IntoIterator<Item = &'a Self>) -> Option<Self> {
+        iter.into_iter()
+            .try_fold(Self::default(), |sum, modules| sum.checked_add(modules))
+    }
+
+    /// Sum of the modules A1 to C4.
+    ///
+    /// Module D lies beyond the system boundary and is not part of the total (EN 15804).
+    pub fn checked_total(&self) -> Option<Decimal> {
+        Decimal::checked_sum(
+            Module::ALL
+                .into_iter()
+                .filter(|&module| module != Module::D)
+                .map(|module| self.get(module)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_and_set_every_module() {
+        let mut modules = LifeCycleModules::default();
+        for (index, module) in Module::ALL.into_iter().enumerate() {
+            *modules.get_mut(module) = Decimal::from_int(index as i64 + 1);
+        }
+        for (index, module) in Module::ALL.into_iter().enumerate() {
+            assert_eq!(modules.get(module), Decimal::from_int(index as i64 + 1));
+        }
+        assert_eq!(modules.a1_a3, Decimal::from_int(1));
+        assert_eq!(modules.b6, Decimal::from_int(9));
+        assert_eq!(modules.d, Decimal::from_int(15));
+        let single = LifeCycleModules::single(Module::C2, Decimal::new(5, 1));
+        assert_eq!(single.c2, Decimal::new(5, 1));
+        assert_eq!(single.checked_total(), Some(Decimal::new(5, 1)));
+    }
+
+    #[test]
+    fn total_excludes_module_d() {
+        let modules = LifeCycleModules {
+            a1_a3: Decimal::from_int(100),
+            c4: Decimal::new(25, 1),
+            d: Decimal::from_int(-40),
+            ..Default::default()
+        };
+        assert_eq!(modules.checked_total(), Some(Decimal::new(1025, 1)));
+
+        let overflowing = LifeCycleModules {
+            a1_a3: Decimal::from_units(i64::MAX),
+            b1: Decimal::from_units(1),
+            ..Default::default()
+        };
+        assert_eq!(overflowing.checked_total(), None);
+        // the overflow of module D is not part of the total
+        let overflowing_d = LifeCycleModules {
+            a1_a3: Decimal::from_units(i64::MAX),
+            d: Decimal::from_units(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            overflowing_d.checked_total(),
+            Some(Decimal::from_units(i64::MAX))
+        );
+    }
+
+    #[test]
+    fn module_wise_arithmetic() {
+        let modules = LifeCycleModules {
+            a1_a3: Decimal::from_int(2),
+            d: Decimal::from_int(-1),
+            ..Default::default()
+        };
+        let sum = LifeCycleModules::checked_sum(&[modules, modules.checked_mul_int(3).unwrap()]);
+        assert_eq!(
+            sum,
+            Some(LifeCycleModules {
+                a1_a3: Decimal::from_int(8),
+                d: Decimal::from_int(-4),
+                ..Default::default()
+            })
+        );
+        assert_eq!(modules.checked_mul_int(i64::MAX), None);
+    }
+}
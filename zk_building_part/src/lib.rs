@@ -5,34 +5,314 @@
 
 mod guest_methods;
 
+use anyhow::Result;
+use std::env;
+use std::sync::Arc;
 use zk_building_part_guest::{
     BuildingDefinition, BuildingEpd, BuildingPartDefinition, BuildingPartEPD, ConcreteEpd,
-    ConcreteMixture, ZkBuildingEpd, ZkBuildingPartEPD, ZkConcreteEpd,
+    ConcreteMixture, EmissionFactors, FactoryEmission, Indicators, ThresholdDefinition,
+    ThresholdEpd, ZkBuildingEpd, ZkBuildingPartEPD, ZkConcreteEpd, ZkThresholdEpd,
 };
-use zk_epdcalc::{new_config_factory, ConfigFactory};
+use zk_epdcalc::{
+    new_composed_config_factory, new_parameterized_config_factory, ConfigFactory,
+    DatasetRegistry,
+};
+use zk_epdcalc_core::Decimal;
+
+fn gwp(value: Decimal) -> Indicators<Decimal> {
+    Indicators {
+        gwp: value,
+        ..Default::default()
+    }
+}
+
+/// Emission factors compiled into the service, used if a request does not select a dataset.
+///
+/// Only the global warming potential is known for the builtin dataset, the other indicators are
+/// zero.
+fn builtin_emission_factors() -> EmissionFactors {
+    EmissionFactors {
+        cement: gwp(Decimal::new(120, 2)),
+        gravel: gwp(Decimal::new(9, 3)),
+        water: gwp(Decimal::new(5, 3)),
+        additives: gwp(Decimal::new(15, 2)),
+        factories: vec![
+            FactoryEmission {
+                factory: "Wien".into(),
+                impacts: gwp(Decimal::new(39, 1)),
+            },
+            FactoryEmission {
+                factory: "Eggendorf".into(),
+                impacts: gwp(Decimal::new(36, 1)),
+            },
+        ],
+        modules: Indicators::default(),
+    }
+}
+
+/// Loads the emission factor datasets.
+///
+/// Signed datasets are read from the files in `EPD_FACTOR_DATASETS` (separated by `:`) and
+/// verified with the hex encoded Ed25519 public key in `EPD_FACTOR_KEY`.
+pub fn emission_factors_from_env() -> Result<DatasetRegistry<EmissionFactors>> {
+    let key = env::var("EPD_FACTOR_KEY").ok();
+    let mut registry = DatasetRegistry::new(key.as_deref())?
+        .with_builtin("builtin", builtin_emission_factors())?;
+    if let Ok(files) = env::var("EPD_FACTOR_DATASETS") {
+        for file in files.split(':').filter(|file| !file.is_empty()) {
+            registry.register_file(file)?;
+        }
+    }
+    Ok(registry)
+}
 
-pub fn builder() -> Vec<(&'static str, Box<dyn ConfigFactory + Send + Sync>)> {
+pub fn builder(
+    emission_factors: DatasetRegistry<EmissionFactors>,
+) -> Vec<(&'static str, Box<dyn ConfigFactory + Send + Sync>)> {
     vec![
         (
             "Concrete",
-            new_config_factory::<ConcreteMixture, ConcreteEpd, ZkConcreteEpd>(
+            new_parameterized_config_factory::<ConcreteMixture, ConcreteEpd, ZkConcreteEpd>(
                 guest_methods::ZK_EPD_ELF,
                 &guest_methods::ZK_EPD_ID,
+                Arc::new(emission_factors),
             ),
         ),
         (
             "BuildingPart",
-            new_config_factory::<BuildingPartDefinition, BuildingPartEPD, ZkBuildingPartEPD>(
+            new_composed_config_factory::<
+                BuildingPartDefinition,
+                BuildingPartEPD,
+                ZkBuildingPartEPD,
+            >(
                 guest_methods::ZK_BUILDING_PART_ELF,
                 &guest_methods::ZK_BUILDING_PART_ID,
+                &[&guest_methods::ZK_EPD_ID],
             ),
         ),
         (
             "Building",
-            new_config_factory::<BuildingDefinition, BuildingEpd, ZkBuildingEpd>(
+            new_composed_config_factory::<BuildingDefinition, BuildingEpd, ZkBuildingEpd>(
                 guest_methods::ZK_BUILDING_ELF,
                 &guest_methods::ZK_BUILDING_ID,
+                &[&guest_methods::ZK_BUILDING_PART_ID, &guest_methods::ZK_EPD_ID],
+            ),
+        ),
+        (
+            "BuildingThreshold",
+            new_composed_config_factory::<ThresholdDefinition, ThresholdEpd, ZkThresholdEpd>(
+                guest_methods::ZK_THRESHOLD_ELF,
+                &guest_methods::ZK_THRESHOLD_ID,
+                &[
+                    &guest_methods::ZK_BUILDING_ID,
+                    &guest_methods::ZK_BUILDING_PART_ID,
+                    &guest_methods::ZK_EPD_ID,
+                ],
             ),
         ),
     ]
 }
+
+/// Runs the guests in dev mode, the guest methods have to be built.
+#[cfg(all(test, feature = "guest-methods"))]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Method, Request};
+    use axum::Router;
+    use serde_json::{json, Value};
+    use std::collections::HashSet;
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use zk_building_part_guest::LifeCycleModules;
+    use zk_epdcalc::{
+        start_prover_service, IssuerKey, ProvingOptions, StaticDidResolver, StaticKeyResolver,
+    };
+
+    /// Service in dev mode that issues its results as credentials with `format=vc`, so they can
+    /// be used as DPPs of the next request.
+    fn dev_service() -> Router {
+        // module D, beyond the system boundary, lowers the GWP of each unit of concrete
+        let modules = Indicators {
+            gwp: LifeCycleModules {
+                c1: Decimal::new(12, 1),
+                d: Decimal::from_int(-5),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let factors = EmissionFactors {
+            modules,
+            ..builtin_emission_factors()
+        };
+        let emission_factors = DatasetRegistry::new(None)
+            .unwrap()
+            .with_builtin("builtin", factors)
+            .unwrap();
+        let options = ProvingOptions {
+            dev_mode: true,
+            ..Default::default()
+        };
+        let (router, _) = start_prover_service(
+            builder(emission_factors),
+            Arc::new(StaticKeyResolver::default()),
+            HashSet::new(),
+            Arc::new(StaticDidResolver::default()),
+            Some(IssuerKey::from_hex(None, &"05".repeat(32)).unwrap()),
+            options,
+        );
+        router
+    }
+
+    async fn send(router: &Router, method: Method, uri: &str, body: Option<Value>) -> Value {
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(status.is_success(), "{status}: {}", String::from_utf8_lossy(&body));
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Status of the task created by `body`, once it is finished.
+    async fn finished_creation(router: &Router, zk_type: &str, body: Value) -> Value {
+        let uri = format!("/creation?zktype={zk_type}");
+        let created = send(router, Method::POST, &uri, Some(body)).await;
+        let uri = format!("/creation/{}", created["id"].as_str().unwrap());
+        loop {
+            let status = send(router, Method::GET, &uri, None).await;
+            if !matches!(status["state"].as_str(), Some("Submitted" | "Queued" | "InProgress")) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// zkEPD returned by `create`, as DID mapping of a credential with `format=vc`.
+    async fn create(router: &Router, query: &str, body: Value) -> Value {
+        send(router, Method::POST, &format!("/create?{query}"), Some(body)).await
+    }
+
+    /// Credential of a building part made of `amount` units of concrete.
+    async fn building_part_credential(router: &Router, amount: u32) -> Value {
+        let concrete = create(router, "zktype=Concrete&format=vc", mixture(300, 0)).await;
+        let body = json!({
+            "definition": {
+                "date": "2024-10-01",
+                "building": "Building 1",
+                "buildingPartID": "wall-1",
+                "usedMaterial": [{"amount": amount, "concreteDppDid": concrete["did"]}],
+            },
+            "dpps": [concrete],
+        });
+        create(router, "zktype=BuildingPart&format=vc", body).await
+    }
+
+    /// zkEPD of a building that consists of the credential of a `building_part`.
+    async fn building_epd(router: &Router, building_part: &Value) -> Value {
+        let body = json!({
+            "definition": {
+                "date": "2024-10-02",
+                "building": "Building 1",
+                "buildingParts": [{"buildingPartDppDid": building_part["did"]}],
+                "siteEnergy": [],
+            },
+            "buildingPartDpps": [building_part],
+            "siteEnergyDpps": [],
+        });
+        create(router, "zktype=Building", body).await
+    }
+
+    /// Threshold check of the `building` against the `gwp_limit` per m² of the `reference_area`.
+    fn threshold(building: &Value, reference_area: Decimal, gwp_limit: Decimal) -> Value {
+        json!({
+            "buildingEpd": building,
+            "referenceArea": reference_area,
+            "gwpLimit": gwp_limit,
+        })
+    }
+
+    fn mixture(cement: i64, water: i64) -> Value {
+        json!({
+            "description": "C25/30",
+            "cement": cement,
+            "gravel": 700,
+            "water": water,
+            "additives": 0,
+            "material": "concrete",
+            "factory": "Wien",
+        })
+    }
+
+    #[tokio::test]
+    async fn reject_negative_components() {
+        let router = dev_service();
+
+        let status = finished_creation(&router, "Concrete", mixture(300, 0)).await;
+        assert_eq!(status["state"], "Complete", "{status}");
+
+        // sums up to 1000, but the negative water offsets the cement
+        let status = finished_creation(&router, "Concrete", mixture(500, -200)).await;
+        assert_eq!(status["state"], "Failed");
+        assert_eq!(status["error"]["kind"], "GuestAborted");
+        let message = status["error"]["message"].as_str().unwrap();
+        assert!(message.ends_with("Concrete mixture components must not be negative!"));
+    }
+
+    #[tokio::test]
+    async fn compose_building_from_building_parts() {
+        let router = dev_service();
+        let building_part = building_part_credential(&router, 2).await;
+        let part_epd = &building_part["dpp_vp"]["verifiableCredential"][0]["credentialSubject"];
+
+        // the building consists of the verified building part only
+        let building = building_epd(&router, &building_part).await;
+        assert_eq!(building["impacts"], part_epd["impacts"]);
+        assert_eq!(building["concrete_image_id"], part_epd["concrete_image_id"]);
+        assert_eq!(building["factors_digests"], part_epd["factors_digests"]);
+        assert!(building["zkp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn check_gwp_limit_at_the_boundary() {
+        let router = dev_service();
+        let building = building_epd(&router, &building_part_credential(&router, 2).await).await;
+        let gwp: LifeCycleModules =
+            serde_json::from_value(building["impacts"]["gwp"].clone()).unwrap();
+        let total = gwp.checked_total().unwrap();
+        assert!(gwp.d < Decimal::ZERO);
+
+        let area = Decimal::from_int(1);
+        let query = "zktype=BuildingThreshold";
+        let check = create(&router, query, threshold(&building, area, total)).await;
+        assert_eq!(check["within_limit"], true);
+        assert!(check.get("impacts").is_none());
+        let below = total - Decimal::from_units(1);
+        let check = create(&router, query, threshold(&building, area, below)).await;
+        assert_eq!(check["within_limit"], false);
+        // module D is not part of the total, with it the building would stay within the limit
+        let with_d = total + gwp.d;
+        let check = create(&router, query, threshold(&building, area, with_d)).await;
+        assert_eq!(check["within_limit"], false);
+    }
+
+    #[tokio::test]
+    async fn reject_non_positive_reference_areas() {
+        let router = dev_service();
+        let building = building_epd(&router, &building_part_credential(&router, 1).await).await;
+
+        for area in [Decimal::ZERO, Decimal::from_int(-100)] {
+            let body = threshold(&building, area, Decimal::from_int(1000));
+            let status = finished_creation(&router, "BuildingThreshold", body).await;
+            assert_eq!(status["state"], "Failed");
+            assert_eq!(status["error"]["kind"], "GuestAborted");
+            let message = status["error"]["message"].as_str().unwrap();
+            assert!(message.ends_with("Reference area must be positive!"));
+        }
+    }
+}
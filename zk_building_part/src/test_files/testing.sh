@@ -9,7 +9,7 @@ host="http://127.0.0.1:3000"
 
 zk_bp_filename=$(mktemp)
 zk_b_filename=$(mktemp)
-jq '.[0]' testCreateBPZKP.json | curl -fs -X POST "${host}/create?snark=false&zktype=BuildingPart" -H "Content-Type: application/json" -d @- > "$zk_bp_filename"
+jq '.[0]' testCreateBPZKP.json | curl -fs -X POST "${host}/create?snark=false&zktype=BuildingPart&format=vc" -H "Content-Type: application/json" -d @- > "$zk_bp_filename"
 
 if [ $? -ne 0 ]; then
   echo "Error creating ZK_BuildingPartEPD"
@@ -22,7 +22,7 @@ jq 'del(..|.zkp?)' "$zk_bp_filename"
 #jq '.[3]' testCreateBPZKP.json
 
 #jq --argjson dpp "$zk_bp_epd" '.[3]|..|select(.did? == "did1" or .did? == "did2")|..|.credentialSubject?|objects' testCreateBPZKP.json  . += {"ddp_vp": $dpp}
-jq '.[3]' testCreateBPZKP.json | jq --slurpfile dpp "$zk_bp_filename" '.buildingPartDpps.[].dpp_vp.verifiableCredential.[].credentialSubject += $dpp[0]' > "$zk_b_filename"
+jq '.[3]' testCreateBPZKP.json | jq --slurpfile dpp "$zk_bp_filename" '.buildingPartDpps.[].dpp_vp = $dpp[0].dpp_vp' > "$zk_b_filename"
 response=$(curl -fs -X POST "${host}/create?snark=false&zktype=Building" -H "Content-Type: application/json" -d "@$zk_b_filename")
 
 if [ $? -ne 0 ]; then
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use anyhow::Result;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Content addressed storage of proofs, so identical inputs are not proven again.
+pub trait ProofCache: Send + Sync {
+    /// The proof stored for the `key`, `None` if there is none.
+    fn get(&self, key: &CacheKey) -> Result<Option<Vec<u8>>>;
+
+    /// Stores the proof of the `key`.
+    fn insert(&self, key: &CacheKey, proof: &[u8]) -> Result<()>;
+
+    /// Removes the proofs of all guests but those of the hex encoded `image_ids`, e.g. of guests
+    /// that were replaced by a new version.
+    fn prune(&self, image_ids: &[Box<str>]) -> Result<()>;
+}
+
+/// Address of a proof in the [`ProofCache`]. Proofs of a changed guest have another image ID, so
+/// they are never served for the new guest.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// Hex encoded image ID of the guest.
+    pub image_id: Box<str>,
+    /// Hex encoded SHA-256 digest of everything the guest reads.
+    pub input_digest: Box<str>,
+    /// Kind of the receipt, e.g. `Composite`, `Groth16` or `Fake` in dev mode.
+    pub receipt_kind: Box<str>,
+}
+
+/// Keeps the proofs as files in a directory per image ID.
+pub struct FsProofCache {
+    dir: PathBuf,
+}
+
+impl FsProofCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir
+            .join(&*key.image_id)
+            .join(format!("{}-{}.cbor", key.receipt_kind, key.input_digest))
+    }
+}
+
+impl ProofCache for FsProofCache {
+    fn get(&self, key: &CacheKey) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(proof) => Ok(Some(proof)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn insert(&self, key: &CacheKey, proof: &[u8]) -> Result<()> {
+        let path = self.path(key);
+        let dir = path.parent().expect("Cache path has a parent!");
+        fs::create_dir_all(dir)?;
+        // readers never see a partially written proof
+        let partial = dir.join(format!(".{}", Uuid::new_v4()));
+        fs::write(&partial, proof)?;
+        fs::rename(&partial, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        })?;
+        Ok(())
+    }
+
+    fn prune(&self, image_ids: &[Box<str>]) -> Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let in_use = image_ids.iter().any(|image_id| name == **image_id);
+            if !in_use && entry.file_type()?.is_dir() {
+                tracing::info!("Removing cached proofs of image {}", name.to_string_lossy());
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_proofs_per_image() {
+        let dir = std::env::temp_dir().join(format!("proof-cache-{}", Uuid::new_v4()));
+        let cache = FsProofCache::new(&dir);
+        let key = CacheKey {
+            image_id: "aa".into(),
+            input_digest: "bb".into(),
+            receipt_kind: "Composite".into(),
+        };
+        let changed_guest = CacheKey {
+            image_id: "cc".into(),
+            ..key.clone()
+        };
+
+        assert_eq!(cache.get(&key).unwrap(), None);
+        cache.insert(&key, b"proof").unwrap();
+        assert_eq!(
+            cache.get(&key).unwrap().as_deref(),
+            Some(b"proof".as_slice())
+        );
+        assert_eq!(cache.get(&changed_guest).unwrap(), None);
+        assert!(dir.join("aa").join("Composite-bb.cbor").is_file());
+
+        cache.insert(&changed_guest, b"new proof").unwrap();
+        cache.prune(&["cc".into()]).unwrap();
+        assert_eq!(cache.get(&key).unwrap(), None);
+        assert!(!dir.join("aa").exists());
+        assert!(cache.get(&changed_guest).unwrap().is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use anyhow::{bail, Result};
+use risc0_zkvm::ExternalProver;
+use std::fs;
+#[cfg(unix)]
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Started instead of `r0vm`, it does not start `r0vm` any more once the proof was cancelled.
+///
+/// The launcher records its own process id for [`Cancellation::cancel`] and kills `r0vm` when it
+/// receives `SIGTERM`. The process id is removed before the launcher exits; until the prover
+/// waits for it, the launcher's id cannot be reused by another process.
+const LAUNCHER: &str = r#"#!/bin/sh
+dir=$(dirname "$0")
+[ -e "$dir/cancelled" ] && exit 1
+r0vm "$@" &
+r0vm=$!
+trap 'kill -9 $r0vm' TERM
+echo $$ > "$dir/pid"
+[ -e "$dir/cancelled" ] && kill -9 $r0vm
+wait $r0vm
+status=$?
+rm -f "$dir/pid"
+exit $status
+"#;
+
+/// Cancels the generation of a proof.
+///
+/// A proof that has not started yet is skipped, the `r0vm` process of a running proof is killed.
+#[derive(Clone)]
+pub(crate) struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    /// Private directory of the launcher script and its process id.
+    dir: Arc<PathBuf>,
+}
+
+impl Cancellation {
+    pub(crate) fn new(id: Uuid) -> Self {
+        // the name cannot be guessed, so no other user can create the directory beforehand
+        let dir = std::env::temp_dir().join(format!("zk_epdcalc_r0vm_{id}_{}", Uuid::new_v4()));
+        Self {
+            cancelled: Default::default(),
+            dir: Arc::new(dir),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Prover that starts `r0vm` in a way that it can be killed by [`Cancellation::cancel`].
+    pub(crate) fn prover(&self) -> Result<ExternalProver> {
+        if self.is_cancelled() {
+            bail!("Proof generation was cancelled");
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+
+            // fails if the directory exists, only the service can write to it
+            fs::DirBuilder::new().mode(0o700).create(&*self.dir)?;
+            let launcher = self.dir.join("r0vm");
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o700)
+                .open(&launcher)?
+                .write_all(LAUNCHER.as_bytes())?;
+            Ok(ExternalProver::new("ipc", launcher))
+        }
+        #[cfg(not(unix))]
+        Ok(ExternalProver::new("ipc", "r0vm"))
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // fails if the proof has not started or is already finished
+        if fs::write(self.dir.join("cancelled"), []).is_err() {
+            return;
+        }
+        let pid = fs::read_to_string(self.dir.join("pid"))
+            .ok()
+            .and_then(|pid| pid.trim().parse::<i32>().ok());
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            // SAFETY: kill only sends a signal to the launcher of this proof, which kills r0vm
+            unsafe {
+                libc::kill(pid, libc::SIGTERM);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = pid;
+    }
+
+    /// Removes the launcher once the proof is finished.
+    pub(crate) fn finish(&self) {
+        let _ = fs::remove_dir_all(&*self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_proofs_do_not_start() {
+        let cancellation = Cancellation::new(Uuid::new_v4());
+        assert!(cancellation.prover().is_ok());
+        cancellation.cancel();
+        assert!(cancellation.is_cancelled());
+        assert!(cancellation.dir.join("cancelled").exists());
+        assert!(cancellation.prover().is_err());
+        cancellation.finish();
+        assert!(!cancellation.dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_running_provers() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::process::Command;
+        use std::time::{Duration, Instant};
+
+        let cancellation = Cancellation::new(Uuid::new_v4());
+        cancellation.prover().unwrap();
+        let mode = fs::metadata(&*cancellation.dir)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // r0vm that runs until it is killed
+        let bin = std::env::temp_dir().join(format!("zk_epdcalc_bin_{}", Uuid::new_v4()));
+        fs::create_dir(&bin).unwrap();
+        fs::write(bin.join("r0vm"), "#!/bin/sh\nexec sleep 60\n").unwrap();
+        fs::set_permissions(bin.join("r0vm"), fs::Permissions::from_mode(0o700)).unwrap();
+        let path = format!(
+            "{}:{}",
+            bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let mut launcher = Command::new(cancellation.dir.join("r0vm"))
+            .env("PATH", path)
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        while !cancellation.dir.join("pid").exists() {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        cancellation.cancel();
+        assert!(!launcher.wait().unwrap().success());
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(!cancellation.dir.join("pid").exists());
+        cancellation.finish();
+        fs::remove_dir_all(bin).unwrap();
+    }
+}
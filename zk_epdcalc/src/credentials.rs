@@ -0,0 +1,579 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use anyhow::{anyhow, Context, Result};
+use axum::async_trait;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use risc0_zkvm::sha::{Impl, Sha256};
+use serde_json::{json, Map, Value};
+use hex::FromHex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Multicodec prefix of an Ed25519 public key in a multibase encoded Multikey.
+const ED25519_MULTICODEC: [u8; 2] = [0xed, 0x01];
+
+/// Context of W3C Verifiable Credentials Data Model v2.0 credentials.
+const CREDENTIALS_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+
+/// Resolves the `verificationMethod` of a credential proof to the Ed25519 key of the issuer.
+// async_trait marks the boxed future as must_use a second time
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait KeyResolver: Send + Sync {
+    async fn resolve(&self, verification_method: &str) -> Option<VerifyingKey>;
+}
+
+/// Key resolver with a fixed set of verification methods, e.g. loaded from a key file.
+#[derive(Default)]
+pub struct StaticKeyResolver {
+    keys: HashMap<Box<str>, VerifyingKey>,
+}
+
+impl StaticKeyResolver {
+    /// Registers the multibase encoded Ed25519 public key (`z6Mk...`) of a verification method.
+    pub fn insert(&mut self, verification_method: &str, public_key_multibase: &str) -> Result<()> {
+        let key = decode_multikey(public_key_multibase)
+            .with_context(|| format!("Invalid key of verification method {verification_method}"))?;
+        self.keys.insert(verification_method.into(), key);
+        Ok(())
+    }
+
+    /// Loads a JSON file that maps verification methods to multibase encoded public keys.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        let keys: HashMap<Box<str>, Box<str>> = serde_json::from_slice(&json)?;
+        let mut resolver = Self::default();
+        for (verification_method, key) in &keys {
+            resolver.insert(verification_method, key)?;
+        }
+        Ok(resolver)
+    }
+
+    /// DIDs of the verification methods, e.g. as trusted issuers.
+    pub fn issuers(&self) -> impl Iterator<Item = &str> {
+        self.keys
+            .keys()
+            .map(|method| method.split_once('#').map_or(&**method, |(did, _)| did))
+    }
+}
+
+#[async_trait]
+impl KeyResolver for StaticKeyResolver {
+    async fn resolve(&self, verification_method: &str) -> Option<VerifyingKey> {
+        self.keys.get(verification_method).copied()
+    }
+}
+
+/// Decodes a base58btc multibase encoded Ed25519 Multikey.
+pub(crate) fn decode_multikey(key: &str) -> Result<VerifyingKey> {
+    let encoded = key
+        .strip_prefix('z')
+        .ok_or(anyhow!("Key is not base58btc multibase encoded"))?;
+    let bytes = bs58::decode(encoded).into_vec()?;
+    let key = bytes
+        .strip_prefix(&ED25519_MULTICODEC)
+        .ok_or(anyhow!("Key is not an Ed25519 public key"))?;
+    Ok(VerifyingKey::try_from(key)?)
+}
+
+/// Encodes an Ed25519 public key as base58btc multibase Multikey (`z6Mk...`).
+fn encode_multikey(key: &VerifyingKey) -> String {
+    let bytes = [&ED25519_MULTICODEC[..], key.as_bytes()].concat();
+    format!("z{}", bs58::encode(bytes).into_string())
+}
+
+/// Key the service signs its results with when they are requested as verifiable credentials.
+pub struct IssuerKey {
+    verification_method: Box<str>,
+    signing_key: SigningKey,
+}
+
+impl IssuerKey {
+    /// The DID of the issuer is the part of `verification_method` before the fragment.
+    pub fn new(verification_method: &str, signing_key: SigningKey) -> Self {
+        Self {
+            verification_method: verification_method.into(),
+            signing_key,
+        }
+    }
+
+    /// Issuer identified by the `did:key` DID of its signing key.
+    pub fn did_key(signing_key: SigningKey) -> Self {
+        let key = encode_multikey(&signing_key.verifying_key());
+        Self::new(&format!("did:key:{key}#{key}"), signing_key)
+    }
+
+    /// Reads the 32 byte secret key from a hex encoded string, e.g. an environment variable.
+    pub fn from_hex(verification_method: Option<&str>, secret_key: &str) -> Result<Self> {
+        let secret_key = <[u8; 32]>::from_hex(secret_key.trim())
+            .context("Issuer key must be 32 hex encoded bytes")?;
+        let signing_key = SigningKey::from_bytes(&secret_key);
+        Ok(match verification_method {
+            Some(verification_method) => Self::new(verification_method, signing_key),
+            None => Self::did_key(signing_key),
+        })
+    }
+
+    pub fn issuer(&self) -> &str {
+        self.verification_method
+            .split_once('#')
+            .map_or(&self.verification_method, |(did, _)| did)
+    }
+
+    /// Issues a credential about `subject` with an `eddsa-jcs-2022` proof.
+    pub(crate) fn issue(&self, subject: Value) -> Result<Value, CredentialError> {
+        let credential = json!({
+            "@context": [CREDENTIALS_CONTEXT],
+            "type": ["VerifiableCredential"],
+            "issuer": self.issuer(),
+            "credentialSubject": subject,
+        });
+        let proof = json!({
+            "type": "DataIntegrityProof",
+            "cryptosuite": "eddsa-jcs-2022",
+            "verificationMethod": self.verification_method,
+            "proofPurpose": "assertionMethod",
+        });
+        let (Value::Object(mut credential), Value::Object(mut proof)) = (credential, proof) else {
+            unreachable!("Credential and proof are JSON objects");
+        };
+        let hash_data = hash_data(credential.clone(), proof.clone())?;
+        let signature = self.signing_key.sign(&hash_data).to_bytes();
+        proof.insert(
+            "proofValue".into(),
+            format!("z{}", bs58::encode(signature).into_string()).into(),
+        );
+        credential.insert("proof".into(), Value::Object(proof));
+        Ok(Value::Object(credential))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CredentialError {
+    #[error("Credential without proof")]
+    MissingProof,
+    #[error("Unsupported proof: {0}")]
+    UnsupportedProof(Box<str>),
+    #[error("Malformed proof")]
+    MalformedProof,
+    #[error("Unknown verification method {0}")]
+    UnknownVerificationMethod(Box<str>),
+    #[error("Verification method {0} does not belong to the issuer")]
+    IssuerMismatch(Box<str>),
+    #[error("Untrusted issuer {0}")]
+    UntrustedIssuer(Box<str>),
+    #[error("Invalid signature of {0}")]
+    InvalidSignature(Box<str>),
+    #[error("Credential subject is not the DPP {0}")]
+    SubjectMismatch(Box<str>),
+}
+
+/// Verifies the Data Integrity proofs of the verifiable credentials (DPPs) in a request.
+///
+/// Only Ed25519 proofs of the `eddsa-jcs-2022` cryptosuite are supported. A credential is only
+/// accepted if its issuer is one of the `trusted_issuers` and signed it with an `assertionMethod`
+/// proof of its own verification method. Results of the service (`issuer_key`) are trusted too, so
+/// they can be used as DPPs of the next request.
+#[derive(Clone)]
+pub(crate) struct CredentialVerifier {
+    resolver: Arc<dyn KeyResolver>,
+    trusted_issuers: Arc<HashSet<Box<str>>>,
+    issuer_key: Option<Arc<IssuerKey>>,
+}
+
+impl CredentialVerifier {
+    pub(crate) fn new(
+        resolver: Arc<dyn KeyResolver>,
+        mut trusted_issuers: HashSet<Box<str>>,
+        issuer_key: Option<Arc<IssuerKey>>,
+    ) -> Self {
+        trusted_issuers.extend(issuer_key.iter().map(|issuer_key| issuer_key.issuer().into()));
+        Self {
+            resolver,
+            trusted_issuers: Arc::new(trusted_issuers),
+            issuer_key,
+        }
+    }
+
+    async fn resolve(&self, verification_method: &str) -> Option<VerifyingKey> {
+        match &self.issuer_key {
+            Some(issuer_key) if *issuer_key.verification_method == *verification_method => {
+                Some(issuer_key.signing_key.verifying_key())
+            }
+            _ => self.resolver.resolve(verification_method).await,
+        }
+    }
+
+    /// Verifies every credential in a `verifiableCredential` member of the request. The
+    /// `credentialSubject` of a credential in a DID mapping (`{"did": ..., "dpp_vp": ...}`) must be
+    /// the mapped DPP, otherwise any trusted credential could be presented for another DPP.
+    pub(crate) async fn verify_request(&self, request: &Value) -> Result<(), CredentialError> {
+        for (did, credential) in credentials(request, None) {
+            self.verify_credential(credential).await?;
+            if let Some(did) = did {
+                verify_subject(credential, did)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn verify_credential(&self, credential: &Value) -> Result<(), CredentialError> {
+        let mut document = credential
+            .as_object()
+            .cloned()
+            .ok_or(CredentialError::MalformedProof)?;
+        let mut proof = match document.remove("proof") {
+            Some(Value::Object(proof)) => proof,
+            Some(_) => Err(CredentialError::UnsupportedProof("proof set".into()))?,
+            None => Err(CredentialError::MissingProof)?,
+        };
+
+        let proof_type = proof
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let cryptosuite = proof
+            .get("cryptosuite")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if proof_type != "DataIntegrityProof" || cryptosuite != "eddsa-jcs-2022" {
+            Err(CredentialError::UnsupportedProof(
+                format!("{proof_type} {cryptosuite}").trim().into(),
+            ))?;
+        }
+        let proof_purpose = proof
+            .get("proofPurpose")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if proof_purpose != "assertionMethod" {
+            Err(CredentialError::UnsupportedProof(
+                format!("proof purpose {proof_purpose}").trim().into(),
+            ))?;
+        }
+        let proof_value = match proof.remove("proofValue") {
+            Some(Value::String(proof_value)) => proof_value,
+            _ => Err(CredentialError::MalformedProof)?,
+        };
+        let verification_method = proof
+            .get("verificationMethod")
+            .and_then(Value::as_str)
+            .ok_or(CredentialError::MalformedProof)?
+            .to_owned();
+        let issuer = match document.get("issuer") {
+            Some(Value::String(issuer)) => issuer.as_str(),
+            Some(Value::Object(issuer)) => issuer
+                .get("id")
+                .and_then(Value::as_str)
+                .ok_or(CredentialError::MalformedProof)?,
+            _ => Err(CredentialError::MalformedProof)?,
+        };
+        // the DID of a verification method is the DID URL without its fragment
+        let controller = verification_method
+            .split_once('#')
+            .map_or(verification_method.as_str(), |(did, _)| did);
+        if controller != issuer {
+            Err(CredentialError::IssuerMismatch(
+                verification_method.as_str().into(),
+            ))?;
+        }
+        if !self.trusted_issuers.contains(issuer) {
+            Err(CredentialError::UntrustedIssuer(issuer.into()))?;
+        }
+
+        let hash_data = hash_data(document, proof)?;
+        let signature = proof_value
+            .strip_prefix('z')
+            .and_then(|encoded| bs58::decode(encoded).into_vec().ok())
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or(CredentialError::MalformedProof)?;
+        let key = self
+            .resolve(&verification_method)
+            .await
+            .ok_or_else(|| {
+                CredentialError::UnknownVerificationMethod(verification_method.as_str().into())
+            })?;
+        key.verify(&hash_data, &signature)
+            .or(Err(CredentialError::InvalidSignature(
+                verification_method.into(),
+            )))
+    }
+}
+
+/// The credentials of the request with the DID of the mapping they are presented for.
+fn credentials<'a>(
+    request: &'a Value,
+    did: Option<&'a str>,
+) -> Vec<(Option<&'a str>, &'a Value)> {
+    match request {
+        Value::Object(members) => {
+            let did = members.get("did").and_then(Value::as_str).or(did);
+            members
+                .iter()
+                .flat_map(|(name, value)| match (name.as_str(), value) {
+                    ("verifiableCredential", Value::Array(credentials)) => {
+                        credentials.iter().map(|credential| (did, credential)).collect()
+                    }
+                    ("verifiableCredential", credential) => vec![(did, credential)],
+                    (_, value) => credentials(value, did),
+                })
+                .collect()
+        }
+        Value::Array(values) => values
+            .iter()
+            .flat_map(|value| credentials(value, did))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Every subject of the `credential` must have the `id` of the DPP.
+fn verify_subject(credential: &Value, did: &str) -> Result<(), CredentialError> {
+    let subjects = credential.get("credentialSubject").map(one_or_many).unwrap_or_default();
+    if subjects.is_empty() || subjects.iter().any(|subject| subject["id"] != did) {
+        Err(CredentialError::SubjectMismatch(did.into()))?;
+    }
+    Ok(())
+}
+
+/// Data signed by an `eddsa-jcs-2022` proof: the SHA-256 hashes of the JCS canonicalized proof
+/// configuration and of the credential without its proof.
+fn hash_data(
+    mut document: Map<String, Value>,
+    mut proof_options: Map<String, Value>,
+) -> Result<Vec<u8>, CredentialError> {
+    if let Some(context) = proof_options.get("@context") {
+        let document_contexts = document.get("@context").map(one_or_many).unwrap_or_default();
+        if !document_contexts.starts_with(&one_or_many(context)) {
+            Err(CredentialError::MalformedProof)?;
+        }
+        document.insert("@context".into(), context.clone());
+    }
+    if let Some(context) = document.get("@context") {
+        proof_options.insert("@context".into(), context.clone());
+    }
+
+    let canonical = |map: Map<String, Value>| {
+        serde_jcs::to_vec(&Value::Object(map)).or(Err(CredentialError::MalformedProof))
+    };
+    let mut hash_data = Impl::hash_bytes(&canonical(proof_options)?)
+        .as_bytes()
+        .to_vec();
+    hash_data.extend_from_slice(Impl::hash_bytes(&canonical(document)?).as_bytes());
+    Ok(hash_data)
+}
+
+/// The values of a JSON-LD member that is a single value or an array.
+fn one_or_many(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUER: &str = "did:example:issuer";
+    const METHOD: &str = "did:example:issuer#key-1";
+    const DPP: &str = "did:example:dpp";
+
+    fn multikey(signing_key: &SigningKey) -> String {
+        encode_multikey(&signing_key.verifying_key())
+    }
+
+    fn sign(signing_key: &SigningKey, credential: Value) -> Value {
+        sign_with(signing_key, METHOD, "assertionMethod", credential)
+    }
+
+    fn sign_with(
+        signing_key: &SigningKey,
+        verification_method: &str,
+        proof_purpose: &str,
+        mut credential: Value,
+    ) -> Value {
+        let proof = json!({
+            "type": "DataIntegrityProof",
+            "cryptosuite": "eddsa-jcs-2022",
+            "verificationMethod": verification_method,
+            "proofPurpose": proof_purpose,
+        });
+        let document = credential.as_object().unwrap().clone();
+        let hash_data = hash_data(document, proof.as_object().unwrap().clone()).unwrap();
+        let signature = signing_key.sign(&hash_data).to_bytes();
+        let mut proof = proof;
+        proof["proofValue"] = format!("z{}", bs58::encode(signature).into_string()).into();
+        credential["proof"] = proof;
+        credential
+    }
+
+    fn request(credential: Value) -> Value {
+        json!({
+            "definition": {},
+            "dpps": [{"did": DPP, "dpp_vp": {"verifiableCredential": [credential]}}]
+        })
+    }
+
+    fn verifier(signing_key: &SigningKey) -> CredentialVerifier {
+        let path =
+            std::env::temp_dir().join(format!("zk_epdcalc_keys_{}.json", rand::random::<u64>()));
+        fs::write(&path, json!({ METHOD: multikey(signing_key) }).to_string()).unwrap();
+        let resolver = StaticKeyResolver::from_file(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        CredentialVerifier::new(Arc::new(resolver), HashSet::from([ISSUER.into()]), None)
+    }
+
+    #[tokio::test]
+    async fn verify_signed_credentials() {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let verifier = verifier(&signing_key);
+        let credential = sign(
+            &signing_key,
+            json!({
+                "@context": ["https://www.w3.org/ns/credentials/v2"],
+                "type": ["VerifiableCredential"],
+                "issuer": ISSUER,
+                "credentialSubject": {"id": DPP, "gwp": 30}
+            }),
+        );
+
+        let result = verifier.verify_request(&request(credential.clone())).await;
+        assert!(result.is_ok());
+
+        let mut tampered = credential.clone();
+        tampered["credentialSubject"]["gwp"] = 3.into();
+        assert!(matches!(
+            verifier.verify_request(&request(tampered)).await,
+            Err(CredentialError::InvalidSignature(_))
+        ));
+
+        let other_key = SigningKey::from_bytes(&[4; 32]);
+        assert!(matches!(
+            self::verifier(&other_key)
+                .verify_request(&request(credential))
+                .await,
+            Err(CredentialError::InvalidSignature(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reject_unverifiable_credentials() {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let verifier = verifier(&signing_key);
+
+        let unsigned = json!({"credentialSubject": {"gwp": 30}});
+        assert!(matches!(
+            verifier.verify_request(&request(unsigned)).await,
+            Err(CredentialError::MissingProof)
+        ));
+
+        let legacy = json!({"proof": {"type": "Ed25519Signature2020", "proofValue": "z3yz"}});
+        assert!(matches!(
+            verifier.verify_request(&request(legacy)).await,
+            Err(CredentialError::UnsupportedProof(_))
+        ));
+
+        let mut unknown = sign(&signing_key, json!({"issuer": ISSUER, "credentialSubject": {}}));
+        unknown["proof"]["verificationMethod"] = "did:example:issuer#key-2".into();
+        assert!(matches!(
+            verifier.verify_request(&request(unknown)).await,
+            Err(CredentialError::UnknownVerificationMethod(_))
+        ));
+
+        let authentication = sign_with(
+            &signing_key,
+            METHOD,
+            "authentication",
+            json!({"issuer": ISSUER, "credentialSubject": {}}),
+        );
+        assert!(matches!(
+            verifier.verify_request(&request(authentication)).await,
+            Err(CredentialError::UnsupportedProof(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reject_untrusted_issuers() {
+        // anyone can create a did:key and sign a credential with it
+        let forger_key = SigningKey::from_bytes(&[6; 32]);
+        let forged = IssuerKey::did_key(forger_key.clone())
+            .issue(json!({"gwp": 3}))
+            .unwrap();
+        let forged_method = forged["proof"]["verificationMethod"].as_str().unwrap();
+        let mut resolver = StaticKeyResolver::default();
+        resolver
+            .insert(forged_method, &multikey(&forger_key))
+            .unwrap();
+        let trusted_issuers = HashSet::from([ISSUER.into()]);
+        let untrusting = CredentialVerifier::new(Arc::new(resolver), trusted_issuers, None);
+        assert!(matches!(
+            untrusting.verify_request(&request(forged)).await,
+            Err(CredentialError::UntrustedIssuer(_))
+        ));
+
+        // a credential in the name of a trusted issuer, signed with another DID's key
+        let mismatch = sign_with(
+            &forger_key,
+            "did:example:forger#key-1",
+            "assertionMethod",
+            json!({"issuer": {"id": ISSUER}, "credentialSubject": {}}),
+        );
+        assert!(matches!(
+            verifier(&forger_key)
+                .verify_request(&request(mismatch))
+                .await,
+            Err(CredentialError::IssuerMismatch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn issue_verifiable_credentials() {
+        let issuer_key = IssuerKey::from_hex(None, &hex::encode([5; 32])).unwrap();
+        let key = multikey(&SigningKey::from_bytes(&[5; 32]));
+        assert_eq!(issuer_key.issuer(), format!("did:key:{key}"));
+
+        let credential = issuer_key.issue(json!({"id": DPP, "gwp": 30})).unwrap();
+        assert_eq!(credential["issuer"], issuer_key.issuer());
+        assert_eq!(credential["credentialSubject"]["gwp"], 30);
+
+        // results of the service are trusted without resolving its did:key
+        let resolver = Arc::new(StaticKeyResolver::default());
+        let issuer_key = Some(Arc::new(issuer_key));
+        let verifier = CredentialVerifier::new(resolver, HashSet::new(), issuer_key);
+        assert!(verifier.verify_request(&request(credential)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reject_credentials_of_other_subjects() {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let verifier = verifier(&signing_key);
+        let credential = |subject: Value| {
+            sign(&signing_key, json!({"issuer": ISSUER, "credentialSubject": subject}))
+        };
+
+        let other = credential(json!({"id": "did:example:other", "gwp": 3}));
+        assert!(matches!(
+            verifier.verify_request(&request(other)).await,
+            Err(CredentialError::SubjectMismatch(_))
+        ));
+        let anonymous = credential(json!({"gwp": 3}));
+        assert!(matches!(
+            verifier.verify_request(&request(anonymous)).await,
+            Err(CredentialError::SubjectMismatch(_))
+        ));
+        let subjects = credential(json!([{"id": DPP}, {"id": "did:example:other"}]));
+        assert!(matches!(
+            verifier.verify_request(&request(subjects)).await,
+            Err(CredentialError::SubjectMismatch(_))
+        ));
+        let own = credential(json!({"id": DPP, "gwp": 3}));
+        assert!(verifier.verify_request(&request(own)).await.is_ok());
+    }
+}
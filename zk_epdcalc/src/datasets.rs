@@ -0,0 +1,233 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use crate::proving::ProofInput;
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use hex::FromHex;
+use risc0_zkvm::sha::Digest;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use zk_epdcalc_core::digest_parameters;
+
+/// Source of public parameter datasets (e.g. emission factors) that are passed to a guest.
+pub trait ParameterSource: Send + Sync {
+    /// Returns the named dataset or the default dataset if no name is given.
+    fn get(&self, name: Option<&str>) -> Option<Arc<dyn ProofInput + Send + Sync>>;
+
+    fn list(&self) -> Vec<DatasetInfo>;
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DatasetInfo {
+    pub name: Box<str>,
+    pub digest: Digest,
+    pub default: bool,
+    /// `false` for builtin datasets, they are trusted as part of the service.
+    pub signed: bool,
+}
+
+/// Dataset file as published by the dataset provider.
+///
+/// The signature is an Ed25519 signature over the [`signed_message`] of the `name` and the digest
+/// of `data`, the same digest the guest commits to the journal.
+#[derive(Deserialize)]
+struct SignedDataset<D> {
+    name: Box<str>,
+    data: D,
+    signature: Box<str>,
+}
+
+struct Dataset<D> {
+    data: Arc<D>,
+    digest: Digest,
+    signed: bool,
+}
+
+/// Message signed by the dataset provider: the digest of the data followed by the UTF-8 name.
+/// Signing the name too keeps a signed dataset from being registered under another name.
+fn signed_message(name: &str, digest: &Digest) -> Vec<u8> {
+    [digest.as_bytes(), name.as_bytes()].concat()
+}
+
+pub struct DatasetRegistry<D> {
+    verifying_key: Option<VerifyingKey>,
+    default: Option<Box<str>>,
+    datasets: HashMap<Box<str>, Dataset<D>>,
+}
+
+impl<D> DatasetRegistry<D>
+where
+    D: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Creates a registry that accepts datasets signed by the hex encoded Ed25519 `verifying_key`.
+    pub fn new(verifying_key: Option<&str>) -> Result<Self> {
+        let verifying_key = verifying_key
+            .map(|key| {
+                let bytes = <[u8; 32]>::from_hex(key).context("Invalid dataset verifying key")?;
+                VerifyingKey::from_bytes(&bytes).context("Invalid dataset verifying key")
+            })
+            .transpose()?;
+        Ok(Self {
+            verifying_key,
+            default: None,
+            datasets: HashMap::new(),
+        })
+    }
+
+    /// Registers a dataset that is compiled into the service and therefore not signed.
+    ///
+    /// The first registered dataset is used if a request does not select one.
+    pub fn with_builtin(mut self, name: &str, data: D) -> Result<Self> {
+        self.insert(name.into(), data, false)?;
+        Ok(self)
+    }
+
+    /// Registers a signed dataset from its JSON representation.
+    pub fn register(&mut self, json: &[u8]) -> Result<()> {
+        let SignedDataset {
+            name,
+            data,
+            signature,
+        }: SignedDataset<D> = serde_json::from_slice(json)?;
+        let verifying_key = self
+            .verifying_key
+            .ok_or(anyhow!("No verifying key for dataset {name} configured"))?;
+
+        let digest = digest_parameters(&data)?;
+        let signature = BASE64_STANDARD
+            .decode(&*signature)
+            .context("Unable to decode dataset signature")?;
+        let signature = Signature::from_slice(&signature)?;
+        verifying_key
+            .verify(&signed_message(&name, &digest), &signature)
+            .with_context(|| format!("Invalid signature of dataset {name}"))?;
+
+        self.insert(name, data, true)
+    }
+
+    /// Registers a signed dataset from a JSON file.
+    pub fn register_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        self.register(&json)
+    }
+
+    fn insert(&mut self, name: Box<str>, data: D, signed: bool) -> Result<()> {
+        if self.datasets.contains_key(&name) {
+            Err(anyhow!("Dataset {name} is already registered"))?;
+        }
+        let digest = digest_parameters(&data)?;
+        self.default.get_or_insert_with(|| name.clone());
+        let data = Arc::new(data);
+        self.datasets.insert(
+            name,
+            Dataset {
+                data,
+                digest,
+                signed,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl<D> ParameterSource for DatasetRegistry<D>
+where
+    D: Serialize + Send + Sync + 'static,
+{
+    fn get(&self, name: Option<&str>) -> Option<Arc<dyn ProofInput + Send + Sync>> {
+        let name = name.or(self.default.as_deref())?;
+        self.datasets
+            .get(name)
+            .map(|dataset| dataset.data.clone() as Arc<dyn ProofInput + Send + Sync>)
+    }
+
+    fn list(&self) -> Vec<DatasetInfo> {
+        self.datasets
+            .iter()
+            .map(|(name, dataset)| DatasetInfo {
+                name: name.clone(),
+                digest: dataset.digest,
+                default: self.default.as_ref() == Some(name),
+                signed: dataset.signed,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[derive(Serialize, Deserialize)]
+    struct Factors {
+        cement: u32,
+    }
+
+    fn signed_dataset(signing_key: &SigningKey, cement: u32) -> Vec<u8> {
+        signed_dataset_named(signing_key, "signed", "signed", cement)
+    }
+
+    /// Dataset that is signed as `signed_name` but published as `name`.
+    fn signed_dataset_named(
+        signing_key: &SigningKey,
+        signed_name: &str,
+        name: &str,
+        cement: u32,
+    ) -> Vec<u8> {
+        let digest = digest_parameters(&Factors { cement }).unwrap();
+        let signature = signing_key.sign(&signed_message(signed_name, &digest));
+        serde_json::to_vec(&serde_json::json!({
+            "name": name,
+            "data": { "cement": cement },
+            "signature": BASE64_STANDARD.encode(signature.to_bytes()),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn register_signed_dataset() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let key = hex::encode(signing_key.verifying_key().as_bytes());
+        let mut registry = DatasetRegistry::<Factors>::new(Some(&key))
+            .unwrap()
+            .with_builtin("builtin", Factors { cement: 1 })
+            .unwrap();
+
+        registry
+            .register(&signed_dataset(&signing_key, 2))
+            .unwrap();
+
+        assert!(registry.get(Some("signed")).is_some());
+        assert!(registry.get(None).is_some());
+        assert!(registry.get(Some("unknown")).is_none());
+        let default = registry.list().into_iter().find(|info| info.default).unwrap();
+        assert_eq!(&*default.name, "builtin");
+        assert!(!default.signed);
+    }
+
+    #[test]
+    fn reject_invalid_signature() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        let key = hex::encode(signing_key.verifying_key().as_bytes());
+        let mut registry = DatasetRegistry::<Factors>::new(Some(&key)).unwrap();
+
+        assert!(registry.register(&signed_dataset(&other_key, 2)).is_err());
+        assert!(registry.get(Some("signed")).is_none());
+
+        let renamed = signed_dataset_named(&signing_key, "signed", "renamed", 2);
+        assert!(registry.register(&renamed).is_err());
+        assert!(registry.get(Some("renamed")).is_none());
+    }
+}
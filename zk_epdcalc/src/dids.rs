@@ -0,0 +1,479 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use crate::credentials::{decode_multikey, KeyResolver};
+use crate::outbound::OutboundClient;
+use anyhow::Context;
+use axum::async_trait;
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Service type of the DID document entry that links or embeds the DPP presentation of a DID.
+pub const DPP_SERVICE_TYPE: &str = "DigitalProductPassport";
+
+#[derive(Error, Debug)]
+pub enum DidError {
+    #[error("Unsupported DID method: {0}")]
+    UnsupportedMethod(Box<str>),
+    #[error("Invalid DID: {0}")]
+    InvalidDid(Box<str>),
+    #[error("DID {0} could not be resolved: {1}")]
+    ResolutionFailed(Box<str>, Box<str>),
+    #[error("DID document of {0} does not provide a DPP presentation")]
+    MissingPresentation(Box<str>),
+}
+
+impl DidError {
+    fn resolution_failed(did: &str, error: impl ToString) -> Self {
+        Self::ResolutionFailed(did.into(), error.to_string().into())
+    }
+
+    /// Failed request for a document of the `did`. The cause is only logged, the response must not
+    /// reveal what a host returned.
+    fn fetch_failed(did: &str, error: anyhow::Error) -> Self {
+        tracing::warn!("Document of {did} could not be fetched: {error:#}");
+        Self::resolution_failed(did, "document could not be fetched")
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DidDocument {
+    pub id: Box<str>,
+    #[serde(rename = "verificationMethod", default)]
+    pub verification_method: Vec<VerificationMethod>,
+    #[serde(default)]
+    pub service: Vec<Service>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct VerificationMethod {
+    pub id: Box<str>,
+    #[serde(rename = "type")]
+    pub method_type: Box<str>,
+    pub controller: Box<str>,
+    #[serde(rename = "publicKeyMultibase")]
+    pub public_key_multibase: Option<Box<str>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Service {
+    pub id: Box<str>,
+    #[serde(rename = "type")]
+    pub service_type: Box<str>,
+    /// URL of the resource or the embedded resource itself.
+    #[serde(rename = "serviceEndpoint")]
+    pub service_endpoint: Value,
+}
+
+impl DidDocument {
+    /// Public key of a verification method, whose id may be relative to the document.
+    pub fn public_key(&self, verification_method: &str) -> Option<VerifyingKey> {
+        self.verification_method
+            .iter()
+            .find(|method| {
+                *method.id == *verification_method
+                    || method.id.starts_with('#')
+                        && verification_method.strip_prefix(&*self.id) == Some(&*method.id)
+            })
+            .and_then(|method| method.public_key_multibase.as_deref())
+            .and_then(|key| decode_multikey(key).ok())
+    }
+
+    fn dpp_service(&self) -> Option<&Service> {
+        self.service
+            .iter()
+            .find(|service| &*service.service_type == DPP_SERVICE_TYPE)
+    }
+}
+
+/// Resolves a DID to its DID document.
+// async_trait marks the boxed future as must_use a second time
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait DidResolver: Send + Sync {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError>;
+}
+
+#[async_trait]
+impl<R: DidResolver + ?Sized> DidResolver for Arc<R> {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError> {
+        (**self).resolve(did).await
+    }
+}
+
+/// Verification methods of DIDs are resolved with their DID documents.
+#[async_trait]
+impl<R: DidResolver> KeyResolver for R {
+    async fn resolve(&self, verification_method: &str) -> Option<VerifyingKey> {
+        let (did, _) = verification_method.split_once('#')?;
+        let document = DidResolver::resolve(self, did).await.ok()?;
+        document.public_key(verification_method)
+    }
+}
+
+/// Resolves `did:key` DIDs of Ed25519 keys, whose document is derived from the DID itself.
+pub struct DidKey;
+
+#[async_trait]
+impl DidResolver for DidKey {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError> {
+        let key = did
+            .strip_prefix("did:key:")
+            .ok_or_else(|| DidError::InvalidDid(did.into()))?;
+        decode_multikey(key).map_err(|error| DidError::resolution_failed(did, error))?;
+        Ok(DidDocument {
+            id: did.into(),
+            verification_method: vec![VerificationMethod {
+                id: format!("{did}#{key}").into(),
+                method_type: "Multikey".into(),
+                controller: did.into(),
+                public_key_multibase: Some(key.into()),
+            }],
+            service: Vec::new(),
+        })
+    }
+}
+
+/// Resolves `did:web` DIDs by fetching `did.json` from the web server of the domain, which has
+/// to be a public host.
+#[derive(Default)]
+pub struct DidWeb {
+    client: OutboundClient,
+}
+
+/// URL of the DID document of a `did:web` DID.
+fn did_web_url(did: &str) -> Result<String, DidError> {
+    let mut segments = did
+        .strip_prefix("did:web:")
+        .ok_or_else(|| DidError::InvalidDid(did.into()))?
+        .split(':');
+    let domain = segments
+        .next()
+        .filter(|domain| !domain.is_empty())
+        .ok_or_else(|| DidError::InvalidDid(did.into()))?
+        .replace("%3A", ":");
+    let path: Vec<&str> = segments.collect();
+    if path.is_empty() {
+        Ok(format!("https://{domain}/.well-known/did.json"))
+    } else {
+        Ok(format!("https://{domain}/{}/did.json", path.join("/")))
+    }
+}
+
+#[async_trait]
+impl DidResolver for DidWeb {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError> {
+        let document: DidDocument = self
+            .client
+            .get_json(&did_web_url(did)?)
+            .await
+            .map_err(|error| DidError::fetch_failed(did, error))?;
+        if *document.id != *did {
+            Err(DidError::resolution_failed(
+                did,
+                "document id does not match",
+            ))?;
+        }
+        Ok(document)
+    }
+}
+
+/// Resolves `did:oyd` DIDs with a Universal Resolver compatible endpoint of an OYDID repository.
+pub struct DidOyd {
+    client: OutboundClient,
+    base_url: Box<str>,
+}
+
+impl DidOyd {
+    pub const DEFAULT_BASE_URL: &'static str = "https://oydid.ownyourdata.eu";
+
+    /// The host of the `base_url` may be internal, e.g. a resolver in the same network.
+    pub fn new(base_url: &str) -> Self {
+        let host = Url::parse(base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(Box::from));
+        Self {
+            client: OutboundClient::new(host.into_iter().collect()),
+            base_url: base_url.trim_end_matches('/').into(),
+        }
+    }
+}
+
+impl Default for DidOyd {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BASE_URL)
+    }
+}
+
+/// URL of the resolution result of a `did:oyd` DID. The DID is percent-encoded as a single path
+/// segment, so it cannot change the path or query of the request.
+fn did_oyd_url(base_url: &str, did: &str) -> Result<Url, DidError> {
+    let mut url = Url::parse(base_url).map_err(|error| DidError::resolution_failed(did, error))?;
+    url.path_segments_mut()
+        .map_err(|_| DidError::resolution_failed(did, "invalid resolver URL"))?
+        .pop_if_empty()
+        .extend(["1.0", "identifiers", did]);
+    Ok(url)
+}
+
+#[derive(Deserialize)]
+struct ResolutionResult {
+    #[serde(rename = "didDocument")]
+    did_document: DidDocument,
+}
+
+#[async_trait]
+impl DidResolver for DidOyd {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError> {
+        if !did.starts_with("did:oyd:") {
+            Err(DidError::InvalidDid(did.into()))?;
+        }
+        let url = did_oyd_url(&self.base_url, did)?;
+        let result: ResolutionResult = self
+            .client
+            .get_json(url.as_str())
+            .await
+            .map_err(|error| DidError::fetch_failed(did, error))?;
+        Ok(result.did_document)
+    }
+}
+
+/// Resolver with a fixed set of DID documents, e.g. for offline operation and tests.
+#[derive(Default)]
+pub struct StaticDidResolver {
+    documents: HashMap<Box<str>, DidDocument>,
+}
+
+impl StaticDidResolver {
+    pub fn insert(&mut self, document: DidDocument) {
+        self.documents.insert(document.id.clone(), document);
+    }
+
+    /// Loads a JSON file with an array of DID documents.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        let documents: Vec<DidDocument> = serde_json::from_slice(&json)?;
+        let mut resolver = Self::default();
+        documents
+            .into_iter()
+            .for_each(|document| resolver.insert(document));
+        Ok(resolver)
+    }
+}
+
+#[async_trait]
+impl DidResolver for StaticDidResolver {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError> {
+        self.documents
+            .get(did)
+            .cloned()
+            .ok_or_else(|| DidError::resolution_failed(did, "unknown DID"))
+    }
+}
+
+/// Dispatches DIDs to the resolver of their method.
+#[derive(Default)]
+pub struct DidResolverRegistry {
+    methods: HashMap<Box<str>, Arc<dyn DidResolver>>,
+}
+
+impl DidResolverRegistry {
+    /// Registers the resolver of a DID method, e.g. `key` for `did:key` DIDs.
+    pub fn with_method(mut self, method: &str, resolver: Arc<dyn DidResolver>) -> Self {
+        self.methods.insert(method.into(), resolver);
+        self
+    }
+}
+
+#[async_trait]
+impl DidResolver for DidResolverRegistry {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidError> {
+        let method = did
+            .strip_prefix("did:")
+            .and_then(|did| did.split_once(':'))
+            .map(|(method, _)| method)
+            .ok_or_else(|| DidError::InvalidDid(did.into()))?;
+        let resolver = self
+            .methods
+            .get(method)
+            .ok_or_else(|| DidError::UnsupportedMethod(method.into()))?;
+        DidResolver::resolve(resolver.as_ref(), did).await
+    }
+}
+
+/// Loads the DPP presentations that a request only references by DID. Linked presentations have
+/// to be on public hosts.
+#[derive(Clone)]
+pub(crate) struct PresentationLoader {
+    resolver: Arc<dyn DidResolver>,
+    client: OutboundClient,
+}
+
+impl PresentationLoader {
+    pub(crate) fn new(resolver: Arc<dyn DidResolver>) -> Self {
+        Self {
+            resolver,
+            client: OutboundClient::default(),
+        }
+    }
+
+    /// Adds the missing `dpp_vp` to every DID mapping (`{"did": ...}`) of the request.
+    pub(crate) async fn complete_request(&self, request: &mut Value) -> Result<(), DidError> {
+        for mapping in missing_presentations(request) {
+            let did = mapping["did"].as_str().unwrap_or_default().to_owned();
+            let presentation = self.load(&did).await?;
+            mapping.insert("dpp_vp".into(), presentation);
+        }
+        Ok(())
+    }
+
+    async fn load(&self, did: &str) -> Result<Value, DidError> {
+        let document = DidResolver::resolve(self.resolver.as_ref(), did).await?;
+        let service = document
+            .dpp_service()
+            .ok_or_else(|| DidError::MissingPresentation(did.into()))?;
+        match &service.service_endpoint {
+            Value::String(url) => self
+                .client
+                .get_json(url)
+                .await
+                .map_err(|error| DidError::fetch_failed(did, error)),
+            Value::Object(presentation) => Ok(Value::Object(presentation.clone())),
+            _ => Err(DidError::MissingPresentation(did.into())),
+        }
+    }
+}
+
+fn missing_presentations(value: &mut Value) -> Vec<&mut Map<String, Value>> {
+    match value {
+        Value::Object(members) => {
+            if !members.get("did").is_some_and(Value::is_string) {
+                members
+                    .values_mut()
+                    .flat_map(missing_presentations)
+                    .collect()
+            } else if members.contains_key("dpp_vp") {
+                Vec::new()
+            } else {
+                vec![members]
+            }
+        }
+        Value::Array(values) => values.iter_mut().flat_map(missing_presentations).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY: &str = "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+
+    #[test]
+    fn did_web_urls() {
+        assert_eq!(
+            did_web_url("did:web:example.com").unwrap(),
+            "https://example.com/.well-known/did.json"
+        );
+        assert_eq!(
+            did_web_url("did:web:localhost%3A8443:dpp:1").unwrap(),
+            "https://localhost:8443/dpp/1/did.json"
+        );
+        assert!(did_web_url("did:web:").is_err());
+    }
+
+    #[test]
+    fn did_oyd_urls() {
+        let url = |did| {
+            did_oyd_url("https://oydid.example/", did)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            url("did:oyd:zQm123"),
+            "https://oydid.example/1.0/identifiers/did:oyd:zQm123"
+        );
+        assert_eq!(
+            url("did:oyd:a/../../admin?x=1#y"),
+            "https://oydid.example/1.0/identifiers/did:oyd:a%2F..%2F..%2Fadmin%3Fx=1%23y"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_keys_and_presentations() {
+        let registry = DidResolverRegistry::default()
+            .with_method("key", Arc::new(DidKey))
+            .with_method("web", Arc::new(DidWeb::default()));
+        let did_key = format!("did:key:{KEY}");
+        let method = format!("{did_key}#{KEY}");
+        assert!(KeyResolver::resolve(&registry, &method).await.is_some());
+        assert!(matches!(
+            DidResolver::resolve(&registry, "did:example:123").await,
+            Err(DidError::UnsupportedMethod(_))
+        ));
+
+        let mut documents = StaticDidResolver::default();
+        documents.insert(
+            serde_json::from_value(json!({
+                "id": "did:oyd:dpp",
+                "verificationMethod": [{
+                    "id": "#key-doc", "type": "Multikey", "controller": "did:oyd:dpp",
+                    "publicKeyMultibase": KEY
+                }],
+                "service": [{
+                    "id": "#dpp", "type": DPP_SERVICE_TYPE,
+                    "serviceEndpoint": {"verifiableCredential": []}
+                }]
+            }))
+            .unwrap(),
+        );
+        assert!(KeyResolver::resolve(&documents, "did:oyd:dpp#key-doc")
+            .await
+            .is_some());
+
+        let loader = PresentationLoader::new(Arc::new(documents));
+        let mut request = json!({"dpps": [
+            {"did": "did:oyd:dpp"},
+            {"did": "did:oyd:other", "dpp_vp": {"verifiableCredential": []}}
+        ]});
+        loader.complete_request(&mut request).await.unwrap();
+        assert_eq!(
+            request["dpps"][0]["dpp_vp"],
+            json!({"verifiableCredential": []})
+        );
+
+        let mut request = json!({"dpps": [{"did": "did:oyd:unknown"}]});
+        assert!(loader.complete_request(&mut request).await.is_err());
+
+        // the presentation of a DID document must not be fetched from an internal host
+        let mut documents = StaticDidResolver::default();
+        documents.insert(
+            serde_json::from_value(json!({
+                "id": "did:oyd:internal",
+                "service": [{
+                    "id": "#dpp", "type": DPP_SERVICE_TYPE,
+                    "serviceEndpoint": "http://169.254.169.254/latest/meta-data"
+                }]
+            }))
+            .unwrap(),
+        );
+        let loader = PresentationLoader::new(Arc::new(documents));
+        let mut request = json!({"dpps": [{"did": "did:oyd:internal"}]});
+        let err = loader.complete_request(&mut request).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "DID did:oyd:internal could not be resolved: document could not be fetched"
+        );
+    }
+}
@@ -3,14 +3,17 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
+use crate::credentials::CredentialError;
 use crate::handlers::VerificationResponse;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::{Deserialize, Serialize};
 use std::io;
 use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
+use zk_epdcalc_core::AGGREGATE_OVERFLOW;
 
 #[derive(Error, Debug, Clone)]
 pub enum AppError {
@@ -29,6 +32,21 @@ pub enum AppError {
     #[error("Task is not complete")]
     TaskNotCompleted,
 
+    #[error("Task was cancelled")]
+    TaskCancelled,
+
+    #[error("Task has expired, its result is no longer available")]
+    TaskExpired,
+
+    #[error("Missing or invalid admin token")]
+    Unauthorized,
+
+    #[error("Too many proofs in progress, try again later")]
+    QueueFull,
+
+    #[error("Idempotency-Key was already used for a different request")]
+    IdempotencyKeyReused,
+
     #[error("Unable to decode Zero Knowledge Proof")]
     ProofDecodingFailed,
 
@@ -38,6 +56,18 @@ pub enum AppError {
     #[error("Proof could not be verified!")]
     InvalidProof,
 
+    #[error("Receipt is fake, it was created in dev mode!")]
+    FakeReceipt,
+
+    #[error("Proof depends on receipts of unknown guest images!")]
+    UnknownAssumptionImage,
+
+    #[error("Input does not match the commitment of the EPD!")]
+    NonMatchingCommitment,
+
+    #[error("Proof is based on parameters that are not a registered dataset!")]
+    UnknownParameters,
+
     #[error("Invalid Request data!")]
     InvalidRequestData(Arc<str>),
 }
@@ -56,12 +86,21 @@ impl IntoResponse for AppError {
         let status = match self {
             // AppError::MissingZkProof => StatusCode::BAD_REQUEST,
             AppError::MissingResult => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::ProofGenerationFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ProofGenerationFailed(ref err) => err.kind().status_code(),
             AppError::UnknownTask(_) => StatusCode::NOT_FOUND,
             AppError::TaskNotCompleted => StatusCode::BAD_REQUEST,
+            AppError::TaskCancelled => StatusCode::CONFLICT,
+            AppError::TaskExpired => StatusCode::GONE,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::QueueFull => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
             AppError::ProofDecodingFailed => StatusCode::BAD_REQUEST,
             AppError::NonMatchingEPDInfo => StatusCode::OK,
             AppError::InvalidProof => StatusCode::OK,
+            AppError::FakeReceipt => StatusCode::OK,
+            AppError::UnknownAssumptionImage => StatusCode::OK,
+            AppError::NonMatchingCommitment => StatusCode::OK,
+            AppError::UnknownParameters => StatusCode::OK,
             AppError::InvalidRequestData(_) => StatusCode::BAD_REQUEST,
         };
         if let StatusCode::OK = status {
@@ -86,9 +125,205 @@ pub enum ProvingError {
     #[error("Proof generation failed: {0}")]
     ProvingFailed(#[from] anyhow::Error),
 
+    #[error("Proof generation failed: {0}")]
+    GuestAborted(anyhow::Error),
+
     #[error("Proof output could not be deserialized.")]
     JournalDecodingFailed(#[from] risc0_zkvm::serde::Error),
 
     #[error("Proof could not be serialized.")]
-    SerializationFailed(#[from] ciborium::ser::Error<io::Error>)
-}
\ No newline at end of file
+    SerializationFailed(#[from] ciborium::ser::Error<io::Error>),
+
+    #[error("{0}")]
+    AggregateOverflow(Box<str>),
+
+    #[error("Result could not be encoded as JSON.")]
+    EncodingFailed(#[from] serde_json::Error),
+
+    #[error("Result could not be issued as credential: {0}")]
+    IssuanceFailed(Box<str>),
+
+    /// Error of a failed task, see [`TaskError`].
+    #[error("{1}")]
+    Stored(ErrorKind, Box<str>),
+}
+
+impl From<CredentialError> for ProvingError {
+    fn from(err: CredentialError) -> Self {
+        Self::IssuanceFailed(err.to_string().into())
+    }
+}
+
+impl ProvingError {
+    /// Classifies an error of the prover, e.g. a guest abort caused by an overflowing aggregate.
+    /// Only errors that start with the prefix of the prover are guest aborts, so a message that
+    /// merely mentions it elsewhere is not mistaken for one.
+    pub(crate) fn from_prover(err: anyhow::Error) -> Self {
+        let abort = err.chain().find_map(|cause| {
+            cause
+                .to_string()
+                .strip_prefix(GUEST_PANICKED)
+                .map(str::to_owned)
+        });
+        match abort {
+            Some(message) if message.starts_with(AGGREGATE_OVERFLOW) => {
+                Self::AggregateOverflow(message.into())
+            }
+            Some(_) => Self::GuestAborted(err),
+            None => Self::ProvingFailed(err),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> ErrorKind {
+        match self {
+            ProvingError::GuestAborted(_) => ErrorKind::GuestAborted,
+            ProvingError::ProvingFailed(_) => ErrorKind::ProvingFailed,
+            ProvingError::JournalDecodingFailed(_)
+            | ProvingError::SerializationFailed(_)
+            | ProvingError::EncodingFailed(_) => ErrorKind::InvalidOutput,
+            ProvingError::AggregateOverflow(_) => ErrorKind::AggregateOverflow,
+            ProvingError::IssuanceFailed(_) => ErrorKind::IssuanceFailed,
+            ProvingError::Stored(kind, _) => *kind,
+        }
+    }
+}
+
+/// Prefix of the prover error of a guest that panicked, followed by the abort message.
+const GUEST_PANICKED: &str = "Guest panicked: ";
+
+/// Cause of a failed task.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The guest aborted, e.g. because of an invalid recipe or an unknown emission factor.
+    GuestAborted,
+    /// An aggregated impact exceeds the range of the fixed point values.
+    AggregateOverflow,
+    /// The prover failed to generate the proof.
+    ProvingFailed,
+    /// The proof output could not be decoded or encoded.
+    InvalidOutput,
+    /// The proven EPD could not be issued as credential.
+    IssuanceFailed,
+}
+
+impl ErrorKind {
+    /// Status of the response, errors caused by the input are unprocessable.
+    fn status_code(self) -> StatusCode {
+        match self {
+            ErrorKind::GuestAborted | ErrorKind::AggregateOverflow => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Error of a failed task, as reported in its status.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaskError {
+    pub kind: ErrorKind,
+    pub message: Box<str>,
+    /// `zktype` of the guest that was proven.
+    pub guest: Option<Box<str>>,
+}
+
+impl TaskError {
+    pub(crate) fn new(err: &ProvingError, guest: Option<Box<str>>) -> Self {
+        let message = match err {
+            ProvingError::ProvingFailed(err) | ProvingError::GuestAborted(err) => {
+                format!("Proof generation failed: {err:#}")
+            }
+            err => err.to_string(),
+        };
+        Self {
+            kind: err.kind(),
+            message: message.into(),
+            guest,
+        }
+    }
+}
+
+impl From<&TaskError> for AppError {
+    fn from(err: &TaskError) -> Self {
+        let message = err.message.clone();
+        match err.kind {
+            ErrorKind::AggregateOverflow => ProvingError::AggregateOverflow(message).into(),
+            kind => ProvingError::Stored(kind, message).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proving::TaskStatus;
+    use anyhow::anyhow;
+
+    #[test]
+    fn classify_aggregate_overflow() {
+        let err = anyhow!("Guest panicked: {AGGREGATE_OVERFLOW}: impacts of building part");
+        assert!(matches!(
+            ProvingError::from_prover(err),
+            ProvingError::AggregateOverflow(message) if message.ends_with("impacts of building part")
+        ));
+        let err = anyhow!("Guest panicked: Unknown factory!");
+        assert!(matches!(ProvingError::from_prover(err), ProvingError::GuestAborted(_)));
+    }
+
+    #[test]
+    fn classify_only_anchored_markers() {
+        let kind = |err| ProvingError::from_prover(err).kind();
+        let err = anyhow!("Guest panicked: Unknown factory!").context("Proving failed");
+        assert_eq!(kind(err), ErrorKind::GuestAborted);
+        let err = anyhow!("Guest panicked: Unknown factory {AGGREGATE_OVERFLOW}!");
+        assert_eq!(kind(err), ErrorKind::GuestAborted);
+        let err = anyhow!("r0vm server failed after: Guest panicked: {AGGREGATE_OVERFLOW}");
+        assert_eq!(kind(err), ErrorKind::ProvingFailed);
+        let err = anyhow!("Segment of {AGGREGATE_OVERFLOW} could not be read");
+        assert_eq!(kind(err), ErrorKind::ProvingFailed);
+    }
+
+    #[test]
+    fn describe_task_errors() {
+        let err = ProvingError::from_prover(anyhow!("Guest panicked: Unknown factory!"));
+        let error = TaskError::new(&err, Some("Concrete".into()));
+        assert_eq!(error.kind, ErrorKind::GuestAborted);
+        assert_eq!(&*error.message, "Proof generation failed: Guest panicked: Unknown factory!");
+        assert_eq!(error.guest.as_deref(), Some("Concrete"));
+
+        let err = ProvingError::from_prover(anyhow!("r0vm server failed"));
+        assert_eq!(TaskError::new(&err, None).kind, ErrorKind::ProvingFailed);
+
+        let err = anyhow!("Guest panicked: {AGGREGATE_OVERFLOW}: impacts of building part");
+        let error = TaskError::new(&ProvingError::from_prover(err), None);
+        assert_eq!(error.kind, ErrorKind::AggregateOverflow);
+        assert!(matches!(
+            AppError::from(&error),
+            AppError::ProofGenerationFailed(err) if matches!(*err, ProvingError::AggregateOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn same_status_of_stored_errors() {
+        let unprocessable = StatusCode::UNPROCESSABLE_ENTITY;
+        let errors = [
+            (anyhow!("Guest panicked: Unknown factory!"), unprocessable),
+            (anyhow!("Guest panicked: {AGGREGATE_OVERFLOW}: impacts"), unprocessable),
+            (anyhow!("r0vm server failed"), StatusCode::INTERNAL_SERVER_ERROR),
+        ];
+        for (err, status) in errors {
+            let err = ProvingError::from_prover(err);
+            // result of `/creation/:id/result` for the failed task
+            let stored = TaskStatus::Failed(TaskError::new(&err, None))
+                .get_response()
+                .err()
+                .unwrap()
+                .into_response();
+            // response of `/create`
+            let kind = err.kind();
+            let synchronous = AppError::from(err).into_response();
+            assert_eq!(synchronous.status(), status, "{kind:?}");
+            assert_eq!(stored.status(), status, "{kind:?}");
+        }
+    }
+}
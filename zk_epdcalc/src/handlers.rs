@@ -3,39 +3,92 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use crate::error::AppError;
-use crate::proving;
-use crate::proving::{ProvingService, TaskStatus};
-use crate::requests::{ConfigFactoryMap, ExtractConfig, Verify};
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use crate::datasets::DatasetInfo;
+use crate::error::{AppError, TaskError};
+use crate::proving::{
+    Preflight, ProofStats, ProvingService, TaskInfo, TaskQuery, TaskStatus, Work,
+};
+use crate::requests::{ConfigFactoryMap, ExtractCompression, ExtractConfig, Open, Verify};
+use crate::verify::DevMode;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use futures::Stream;
+use risc0_zkvm::InnerReceipt;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
 use uuid::Uuid;
 
 
 pub(crate) async fn create_epd(
-    ExtractConfig(config): ExtractConfig
+    State(service): State<ProvingService>,
+    ExtractConfig(config, request): ExtractConfig,
 ) -> ResponseResult<Response> {
-    println!("Creating epd for {config:?}");
+    tracing::info!(zk_type = request.zk_type(), "Creating EPD");
+
+    let proof_result = service.prove(config, request.cacheable()).await?;
+    Ok(proof_result.to_response())
+}
+
+/// Executes the guest without proving, the input is checked before a proof is requested.
+pub(crate) async fn post_preflight(
+    State(service): State<ProvingService>,
+    ExtractConfig(config, request): ExtractConfig,
+) -> ResponseResult<Json<Preflight>> {
+    tracing::info!(zk_type = request.zk_type(), "Preflight");
+    // a preflight is not a task, it has no callback and is never reused
+    let task_parameters = request.task_parameters();
+    if !task_parameters.is_empty() {
+        Err(AppError::InvalidRequestData(
+            format!("Preflight does not support {}", task_parameters.join(", ")).into(),
+        ))?;
+    }
 
-    let proof_response = proving::generate_epd(config)?;
-    Ok(proof_response.into_response())
+    let preflight = service.preflight(config).await?;
+    Ok(Json(preflight))
 }
 
 pub(crate) async fn start_epd_creation(
     State(service): State<ProvingService>,
-    ExtractConfig(config): ExtractConfig,
+    ExtractConfig(config, request): ExtractConfig,
+) -> ResponseResult<EPDTaskStatus> {
+    tracing::info!(zk_type = request.zk_type(), "Starting EPD creation");
+
+    let work = Work::Prove {
+        config,
+        cacheable: request.cacheable(),
+    };
+    let (id, state) = service.add_task(work, request).await?;
+
+    Ok(EPDTaskStatus::new(id, state))
+}
+
+/// Starts compressing the receipt of a zkEPD to Groth16 in the background, the result is
+/// retrieved like the result of a creation.
+pub(crate) async fn start_compression(
+    State(service): State<ProvingService>,
+    State(dev_mode): State<DevMode>,
+    ExtractCompression(config, request): ExtractCompression,
 ) -> ResponseResult<EPDTaskStatus> {
-    println!("{config:?}");
+    tracing::info!(zk_type = request.zk_type(), "Compressing EPD");
 
-    let id = service.add_task(config);
+    // only zkEPDs that pass `/verify` are compressed
+    config
+        .verify(dev_mode)
+        .map_err(|err| AppError::InvalidRequestData(err.to_string().into()))?;
+    if let InnerReceipt::Groth16(_) = config.receipt()?.inner {
+        Err(AppError::InvalidRequestData(
+            "Receipt is already a Groth16 receipt".into(),
+        ))?;
+    }
+    let (id, state) = service.add_task(Work::Compress(config), request).await?;
 
-    Ok(EPDTaskStatus {
-        id,
-        state: TaskStatus::Submitted,
-    })
+    Ok(EPDTaskStatus::new(id, state))
 }
 
 pub(crate) async fn get_epd_creation(
@@ -45,10 +98,52 @@ pub(crate) async fn get_epd_creation(
     service
         .get_status(id)
         .await
-        .map(|state| EPDTaskStatus {
-            id,
-            state,
-        })
+        .map(|state| EPDTaskStatus::new(id, state))
+}
+
+/// Number of tasks listed by `GET /creation` without a `limit`.
+const DEFAULT_PAGE_SIZE: usize = 100;
+/// Maximal `limit` of `GET /creation`.
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Lists the tasks matching the query, requires the admin token.
+pub(crate) async fn list_epd_creations(
+    State(admin_token): State<AdminToken>,
+    State(service): State<ProvingService>,
+    headers: HeaderMap,
+    Query(mut query): Query<TaskQuery>,
+) -> ResponseResult<Json<CreationList>> {
+    admin_token.authorize(&headers)?;
+    query.limit = Some(query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE));
+    let page = service.list_tasks(query).await;
+    Ok(Json(CreationList {
+        total: page.total,
+        tasks: page.tasks.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Streams the status of a task as Server-Sent Events, starting with the current status. The
+/// stream ends once the task is finished.
+pub(crate) async fn get_epd_events(
+    State(service): State<ProvingService>,
+    Path(id): Path<Uuid>,
+) -> ResponseResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let rx = service.subscribe(id).await?;
+    let events = futures::stream::unfold((Some(rx), true), move |(rx, first)| async move {
+        let mut rx = rx?;
+        // the sender is dropped when the task is deleted
+        if !first {
+            rx.changed().await.ok()?;
+        }
+        let state = rx.borrow_and_update().clone();
+        let rx = (!state.is_finished()).then_some(rx);
+        let event = Event::default()
+            .event("status")
+            .json_data(EPDTaskStatus::new(id, state))
+            .expect("Task status could not be serialized!");
+        Some((Ok(event), (rx, false)))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
 }
 
 pub(crate) async fn get_epd_result(
@@ -59,23 +154,83 @@ pub(crate) async fn get_epd_result(
         .get_status(id)
         .await
         .and_then(|status| status.get_response())?;
-    Ok(response.into_response())
+    Ok(response.to_response())
+}
+
+/// Cancels a task that is not finished, or deletes a finished task and its result.
+pub(crate) async fn delete_epd_creation(
+    State(service): State<ProvingService>,
+    Path(id): Path<Uuid>,
+) -> ResponseResult<Response> {
+    let response = match service.cancel_task(id).await? {
+        Some(state) => EPDTaskStatus::new(id, state).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    };
+    Ok(response)
 }
 
 //#[debug_handler]
 pub(crate) async fn post_verify_epd(
-    State(_): State<ConfigFactoryMap>,
+    State(dev_mode): State<DevMode>,
     Verify(config): Verify,
 ) -> ResponseResult<Json<VerificationResponse>> {
-    println!("Verifying EPD for {config:?}");
+    tracing::info!("Verifying EPD");
+
+    config.verify(dev_mode)?;
+    Ok(Json(VerificationResponse {
+        verified: true,
+        error: None,
+    }))
+}
+
+pub(crate) async fn post_open_epd(
+    State(dev_mode): State<DevMode>,
+    Open(config): Open,
+) -> ResponseResult<Json<VerificationResponse>> {
+    tracing::info!("Opening input commitment of EPD");
 
-    config.verify()?;
+    config.verify(dev_mode)?;
     Ok(Json(VerificationResponse {
         verified: true,
         error: None,
     }))
 }
 
+/// Bearer token of the admin endpoints, `None` if they are disabled.
+#[derive(Clone, Default)]
+pub(crate) struct AdminToken(pub(crate) Option<Arc<str>>);
+
+impl AdminToken {
+    /// Checks the bearer token of the `Authorization` header.
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), AppError> {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match (&self.0, bearer) {
+            (Some(token), Some(bearer)) if tokens_match(token, bearer) => Ok(()),
+            _ => Err(AppError::Unauthorized),
+        }
+    }
+}
+
+/// Compares the tokens in constant time, so the response time reveals nothing about the token.
+/// The digests have the same length, whatever the length of the bearer token.
+fn tokens_match(token: &str, bearer: &str) -> bool {
+    let (token, bearer) = (Sha256::digest(token), Sha256::digest(bearer));
+    let difference = token
+        .iter()
+        .zip(bearer.iter())
+        .fold(0, |difference, (a, b)| difference | (a ^ b));
+    std::hint::black_box(difference) == 0
+}
+
+pub(crate) async fn get_datasets(
+    State(config_factory_map): State<ConfigFactoryMap>,
+) -> Json<HashMap<&'static str, Vec<DatasetInfo>>> {
+    Json(config_factory_map.datasets())
+}
+
 type ResponseResult<T> = anyhow::Result<T, AppError>;
 
 #[derive(Serialize)]
@@ -84,16 +239,85 @@ pub(crate) struct VerificationResponse {
     pub(crate) error: Option<String>,
 }
 
+/// Response of `GET /creation`.
+#[derive(Serialize)]
+pub(crate) struct CreationList {
+    /// Number of all tasks matching the filters.
+    total: usize,
+    tasks: Vec<CreationInfo>,
+}
+
+/// Status of a task together with its request and timestamps.
+#[derive(Serialize)]
+pub(crate) struct CreationInfo {
+    #[serde(flatten)]
+    status: EPDTaskStatus,
+    zk_type: Option<Box<str>>,
+    snark: Option<bool>,
+    submitted_at: Option<u64>,
+    started_at: Option<u64>,
+    completed_at: Option<u64>,
+    /// Bytes of the stored result.
+    size: usize,
+}
+
+impl From<TaskInfo> for CreationInfo {
+    fn from(task: TaskInfo) -> Self {
+        Self {
+            status: EPDTaskStatus::new(task.id, task.state),
+            zk_type: task.zk_type,
+            snark: task.snark,
+            submitted_at: task.submitted_at,
+            started_at: task.started_at,
+            completed_at: task.completed_at,
+            size: task.size,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct EPDTaskStatus {
     id: Uuid,
     state: TaskStatus,
+    /// Position of a `Queued` task in the queue, starting at 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queue_position: Option<usize>,
+    /// Cause of a `Failed` task.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<TaskError>,
+    /// Prover statistics of a `Complete` task.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<ProofStats>,
+}
+
+impl EPDTaskStatus {
+    pub(crate) fn new(id: Uuid, state: TaskStatus) -> Self {
+        let queue_position = match state {
+            TaskStatus::Queued(position) => Some(position),
+            _ => None,
+        };
+        let error = match &state {
+            TaskStatus::Failed(error) => Some(error.clone()),
+            _ => None,
+        };
+        let stats = match &state {
+            TaskStatus::Complete(result) => result.stats(),
+            _ => None,
+        };
+        Self {
+            id,
+            state,
+            queue_position,
+            error,
+            stats,
+        }
+    }
 }
 
 impl IntoResponse for EPDTaskStatus {
     fn into_response(self) -> Response {
         let status = match self.state {
-            TaskStatus::Submitted => StatusCode::ACCEPTED,
+            TaskStatus::Submitted | TaskStatus::Queued(_) => StatusCode::ACCEPTED,
             _ => StatusCode::OK,
         };
         (status, Json(self)).into_response()
@@ -3,39 +3,111 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use crate::handlers::{create_epd, get_epd_creation, get_epd_result, post_verify_epd, start_epd_creation};
+use crate::handlers::{
+    create_epd, delete_epd_creation, get_datasets, get_epd_creation, get_epd_events,
+    get_epd_result, list_epd_creations, post_open_epd, post_preflight, post_verify_epd,
+    start_compression, start_epd_creation, AdminToken,
+};
+use crate::credentials::CredentialVerifier;
+use crate::dids::PresentationLoader;
 use crate::proving::ProvingService;
 use crate::requests::ConfigFactoryMap;
+use crate::verify::DevMode;
+use crate::webhooks::Webhooks;
 use axum::extract::FromRef;
 use axum::routing::{get, post};
 use axum::Router;
+use std::collections::HashSet;
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 
-pub use requests::{new_config_factory, ConfigFactory};
+pub use cache::{CacheKey, FsProofCache, ProofCache};
+pub use credentials::{CredentialError, IssuerKey, KeyResolver, StaticKeyResolver};
+pub use datasets::{DatasetInfo, DatasetRegistry, ParameterSource};
+pub use dids::{
+    DidDocument, DidError, DidKey, DidOyd, DidResolver, DidResolverRegistry, DidWeb, Service,
+    StaticDidResolver, VerificationMethod, DPP_SERVICE_TYPE,
+};
+pub use proving::ProvingOptions;
+pub use requests::{
+    new_composed_config_factory, new_config_factory, new_parameterized_config_factory,
+    ConfigFactory,
+};
+pub use tasks::{SqliteTaskStore, StoredTask, TaskStore, VolatileTaskStore};
+pub use webhooks::{WebhookOptions, SIGNATURE_HEADER};
 
+mod cache;
+mod cancellation;
+mod credentials;
+mod datasets;
+mod dids;
 mod error;
 mod handlers;
+mod outbound;
 mod proving;
 mod requests;
+mod tasks;
 mod verify;
+mod webhooks;
 
+/// Starts the proving service and returns its router.
+///
+/// DPPs that proof requests only reference by DID are loaded with `did_resolver`, the DPP
+/// credentials are verified with the keys of `key_resolver`. Only credentials of the
+/// `trusted_issuers` (DIDs) and of the service itself are accepted. Results requested with
+/// `format=vc` are signed with `issuer_key`.
+///
+/// The `options` limit the number of proofs generated at the same time and set up the task store,
+/// unfinished tasks of the store are resumed. The task listing `GET /creation` is only available
+/// with an admin token.
 pub fn start_prover_service(
     proof_systems: Vec<(&'static str, Box<dyn ConfigFactory + Sync + Send>)>,
+    key_resolver: Arc<dyn KeyResolver>,
+    trusted_issuers: HashSet<Box<str>>,
+    did_resolver: Arc<dyn DidResolver>,
+    issuer_key: Option<IssuerKey>,
+    options: ProvingOptions,
 ) -> (Router, JoinHandle<()>) {
-    let (proving_service, handle) = ProvingService::new();
-
     let config_factory_map = ConfigFactoryMap::new(proof_systems);
+    let issuer_key = issuer_key.map(Arc::new);
+    let dev_mode = DevMode(options.dev_mode);
+    let admin_token = AdminToken(options.admin_token.as_deref().map(Arc::from));
+    let (proving_service, handle) =
+        ProvingService::new(options, &config_factory_map, &issuer_key);
+    let webhooks = proving_service.webhooks().clone();
+
     let app_state = AppState {
         config_factory_map,
         proving_service,
+        credential_verifier: CredentialVerifier::new(
+            key_resolver,
+            trusted_issuers,
+            issuer_key.clone(),
+        ),
+        presentation_loader: PresentationLoader::new(did_resolver),
+        issuer_key,
+        dev_mode,
+        admin_token,
+        webhooks,
     };
 
     let router = Router::new()
         .route("/create", post(create_epd))
-        .route("/creation", post(start_epd_creation))
-        .route("/creation/:id", get(get_epd_creation))
+        .route(
+            "/creation",
+            get(list_epd_creations).post(start_epd_creation),
+        )
+        .route("/preflight", post(post_preflight))
+        .route("/compress", post(start_compression))
+        .route(
+            "/creation/:id",
+            get(get_epd_creation).delete(delete_epd_creation),
+        )
+        .route("/creation/:id/events", get(get_epd_events))
         .route("/creation/:id/result", get(get_epd_result))
         .route("/verify", post(post_verify_epd))
+        .route("/open", post(post_open_epd))
+        .route("/datasets", get(get_datasets))
         .with_state(app_state);
     (router, handle)
 }
@@ -44,4 +116,10 @@ pub fn start_prover_service(
 struct AppState {
     proving_service: ProvingService,
     config_factory_map: ConfigFactoryMap,
+    credential_verifier: CredentialVerifier,
+    presentation_loader: PresentationLoader,
+    issuer_key: Option<Arc<IssuerKey>>,
+    dev_mode: DevMode,
+    admin_token: AdminToken,
+    webhooks: Webhooks,
 }
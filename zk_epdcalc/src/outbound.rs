@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use anyhow::{bail, Result};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Timeout of a request, from connecting until the response is read.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximal size of a fetched document in bytes.
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// HTTP client for URLs that clients choose, e.g. callbacks, `did:web` documents and the DPP
+/// presentations of DID documents. Only public hosts are requested, so clients cannot reach
+/// internal services through the prover.
+#[derive(Clone)]
+pub(crate) struct OutboundClient {
+    client: reqwest::Client,
+    /// Hosts that may be requested although they are loopback, private or link-local addresses.
+    allowed_hosts: Arc<[Box<str>]>,
+}
+
+impl Default for OutboundClient {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl OutboundClient {
+    pub(crate) fn new(allowed_hosts: Vec<Box<str>>) -> Self {
+        Self {
+            client: client(reqwest::Client::builder()),
+            allowed_hosts: allowed_hosts.into(),
+        }
+    }
+
+    /// `true` if the `url` may be requested: an http(s) URL whose host is not a loopback, private
+    /// or link-local address, unless it is allowed. Host names are checked again when they are
+    /// resolved for the request.
+    pub(crate) fn is_allowed(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        if self.is_allowed_host(host) {
+            return true;
+        }
+        match ip_address(host) {
+            Some(ip) => is_public(ip),
+            None => {
+                let host = host.trim_end_matches('.');
+                host != "localhost" && !host.ends_with(".localhost")
+            }
+        }
+    }
+
+    fn is_allowed_host(&self, host: &str) -> bool {
+        self.allowed_hosts.iter().any(|allowed| **allowed == *host)
+    }
+
+    /// Client that connects to the resolved public addresses of the host of the `url`, so a host
+    /// name cannot be resolved to an internal address between the check and the request.
+    pub(crate) async fn client_for(&self, url: &str) -> Result<reqwest::Client> {
+        if !self.is_allowed(url) {
+            bail!("URL {url} is not allowed");
+        }
+        let url = Url::parse(url)?;
+        let domain = url.host_str().unwrap_or_default();
+        if self.is_allowed_host(domain) || ip_address(domain).is_some() {
+            return Ok(self.client.clone());
+        }
+        let port = url.port_or_known_default().unwrap_or_default();
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port)).await?.collect();
+        if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+            bail!("{domain} resolves to the internal address {}", addr.ip());
+        }
+        if addrs.is_empty() {
+            bail!("{domain} could not be resolved");
+        }
+        Ok(client(
+            reqwest::Client::builder().resolve_to_addrs(domain, &addrs),
+        ))
+    }
+
+    /// Fetches the JSON document at the `url`, documents larger than [`MAX_RESPONSE_SIZE`] are
+    /// rejected.
+    pub(crate) async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let mut response = self
+            .client_for(url)
+            .await?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?;
+        if response
+            .content_length()
+            .is_some_and(|length| length > MAX_RESPONSE_SIZE as u64)
+        {
+            bail!("Response of {url} is too large");
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > MAX_RESPONSE_SIZE {
+                bail!("Response of {url} is too large");
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+/// Redirects are not followed, they could lead to an internal address.
+fn client(builder: reqwest::ClientBuilder) -> reqwest::Client {
+    builder
+        .redirect(Policy::none())
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("HTTP client could not be created!")
+}
+
+/// The IP address of a URL host, IPv6 addresses are enclosed in brackets.
+fn ip_address(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// `false` for loopback, private, link-local, shared and other non-public addresses.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || first == 0
+                // shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (first == 100 && second & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10 addresses
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn limit_size_of_documents() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new()
+            .route(
+                "/small",
+                get(|| async { axum::Json(json!({"id": "did:web:a"})) }),
+            )
+            .route(
+                "/large",
+                get(|| async { "x".repeat(MAX_RESPONSE_SIZE + 1) }),
+            );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        assert!(OutboundClient::default()
+            .get_json::<Value>(&format!("{base_url}/small"))
+            .await
+            .is_err());
+        let client = OutboundClient::new(vec!["127.0.0.1".into()]);
+        let small: Value = client.get_json(&format!("{base_url}/small")).await.unwrap();
+        assert_eq!(small["id"], "did:web:a");
+        let err = client
+            .get_json::<Value>(&format!("{base_url}/large"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("is too large"), "{err}");
+    }
+}
@@ -3,24 +3,38 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use anyhow::{Error, Result};
+use anyhow::{bail, Error, Result};
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine;
-use risc0_zkvm::{ExecutorEnv, ExecutorEnvBuilder, ExternalProver, Prover, ProverOpts, Receipt};
-use serde::{Serialize, Serializer};
-use std::collections::HashMap;
+use hex::FromHex;
+use risc0_zkvm::sha::Digestible;
+use risc0_zkvm::{
+    compute_image_id, default_executor, Executor, ExecutorEnv, ExecutorEnvBuilder, ExitCode,
+    FakeReceipt, InnerReceipt, Journal, Prover, ProverOpts, Receipt, ReceiptClaim, SessionStats,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use sha2::{Digest as _, Sha256};
+use std::collections::{HashMap, VecDeque};
 use tokio::{
     sync::{
         mpsc::{self, UnboundedReceiver, UnboundedSender},
-        oneshot,
+        oneshot, watch, Semaphore,
     },
     task::{JoinHandle, JoinSet},
-    time::Instant,
+    time::{Instant, MissedTickBehavior},
 };
 use uuid::Uuid;
 
+use crate::cache::{CacheKey, ProofCache};
+use crate::cancellation::Cancellation;
+use crate::credentials::IssuerKey;
 use crate::error::AppError;
-use crate::error::ProvingError;
+use crate::error::{ProvingError, TaskError};
+use crate::requests::{ConfigFactoryMap, ProofRequest};
+use crate::tasks::{unix_time, StoredTask, TaskStore, VolatileTaskStore};
+use crate::verify::VerifyConfig;
+use crate::webhooks::{WebhookOptions, Webhooks};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use futures::StreamExt;
@@ -28,24 +42,66 @@ use serde::de::DeserializeOwned;
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::oneshot::Sender;
-use zk_epdcalc_core::VerifiedEpd;
+use zk_epdcalc_core::{ComposedEpd, ImageId, Salt, VerifiedEpd};
+
+/// Response header that carries the salt opening the input commitment of a proven EPD.
+pub(crate) const SALT_HEADER: &str = "commitment-salt";
+
+/// Period in which finished tasks are evicted, in addition to each command.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub(crate) enum TaskStatus {
     Submitted,
+    /// Waiting for a free worker, at the given position of the queue (starting at 1).
+    Queued(usize),
     InProgress,
-    //Cancelled,
-    Complete(Result<Arc<dyn ProofResponse>, AppError>),
+    Cancelled,
+    Complete(Arc<ProofResult>),
+    Failed(TaskError),
+    /// The finished task was evicted after the retention period or to keep the number of
+    /// finished tasks within the limit.
+    Expired,
 }
 
 impl TaskStatus {
-    pub(crate) fn get_response(self) -> Result<Arc<dyn ProofResponse>, AppError> {
+    pub(crate) fn get_response(self) -> Result<Arc<ProofResult>, AppError> {
+        match self {
+            TaskStatus::Submitted | TaskStatus::Queued(_) | TaskStatus::InProgress => {
+                Err(AppError::TaskNotCompleted)
+            }
+            TaskStatus::Cancelled => Err(AppError::TaskCancelled),
+            TaskStatus::Complete(response) => Ok(response),
+            TaskStatus::Failed(err) => Err((&err).into()),
+            TaskStatus::Expired => Err(AppError::TaskExpired),
+        }
+    }
+
+    /// Name of the state, as serialized.
+    pub(crate) fn name(&self) -> &'static str {
         match self {
-            TaskStatus::Submitted | TaskStatus::InProgress => Err(AppError::TaskNotCompleted),
-            TaskStatus::Complete(response) => response,
+            TaskStatus::Submitted => "Submitted",
+            TaskStatus::Queued(_) => "Queued",
+            TaskStatus::InProgress => "InProgress",
+            TaskStatus::Cancelled => "Cancelled",
+            TaskStatus::Complete(_) => "Complete",
+            TaskStatus::Failed(_) => "Failed",
+            TaskStatus::Expired => "Expired",
         }
     }
+
+    /// `true` if the state of the task does not change any more.
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Cancelled
+                | TaskStatus::Complete(_)
+                | TaskStatus::Failed(_)
+                | TaskStatus::Expired
+        )
+    }
 }
 
 impl Serialize for TaskStatus {
@@ -53,27 +109,132 @@ impl Serialize for TaskStatus {
     where
         S: Serializer,
     {
-        let repr = match self {
-            TaskStatus::Submitted => "Submitted",
-            TaskStatus::InProgress => "InProgress",
-            TaskStatus::Complete(_) => "Complete",
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// Outcome of a task in the [`TaskStore`].
+#[derive(Serialize, Deserialize)]
+enum StoredResult {
+    Complete(Arc<ProofResult>),
+    Failed(TaskError),
+    Cancelled,
+    /// Replaces the result of an evicted task.
+    Expired,
+}
+
+/// What a finished task keeps of its request in the [`TaskStore`], without the private input and
+/// salt that are only needed to resume an unfinished task.
+#[derive(Serialize, Deserialize, Default)]
+struct TaskSummary {
+    zk_type: Option<Box<str>>,
+    snark: Option<bool>,
+    /// Hex encoded [`ProofRequest::fingerprint`].
+    fingerprint: Option<String>,
+    idempotency_key: Option<Box<str>>,
+}
+
+/// Overview of a task for the task listing.
+pub(crate) struct TaskInfo {
+    pub(crate) id: Uuid,
+    pub(crate) state: TaskStatus,
+    /// `zktype` of the request, `None` if the stored request could not be read.
+    pub(crate) zk_type: Option<Box<str>>,
+    pub(crate) snark: Option<bool>,
+    /// Seconds since the Unix epoch, `None` for tasks restored from an older task store.
+    pub(crate) submitted_at: Option<u64>,
+    /// Seconds since the Unix epoch, `None` for tasks that were not started since the service
+    /// started.
+    pub(crate) started_at: Option<u64>,
+    pub(crate) completed_at: Option<u64>,
+    /// Bytes of the stored result.
+    pub(crate) size: usize,
+}
+
+/// Filters and page of a task listing, all given filters have to match.
+#[derive(Default, Deserialize)]
+pub(crate) struct TaskQuery {
+    #[serde(rename = "zktype")]
+    zk_type: Option<Box<str>>,
+    state: Option<Box<str>>,
+    snark: Option<bool>,
+    /// Seconds since the Unix epoch, inclusive.
+    submitted_after: Option<u64>,
+    /// Seconds since the Unix epoch, exclusive.
+    submitted_before: Option<u64>,
+    /// Number of matching tasks that are skipped.
+    #[serde(default)]
+    offset: usize,
+    /// Maximal number of listed tasks, `None` for all.
+    pub(crate) limit: Option<usize>,
+}
+
+impl TaskQuery {
+    fn matches(&self, task: &TaskInfo) -> bool {
+        let submitted = |check: fn(u64, u64) -> bool, bound: Option<u64>| {
+            bound.is_none_or(|bound| task.submitted_at.is_some_and(|time| check(time, bound)))
         };
-        serializer.serialize_str(repr)
+        self.zk_type
+            .as_ref()
+            .is_none_or(|zk_type| task.zk_type.as_ref() == Some(zk_type))
+            && self
+                .state
+                .as_deref()
+                .is_none_or(|state| task.state.name() == state)
+            && self.snark.is_none_or(|snark| task.snark == Some(snark))
+            && submitted(|time, after| time >= after, self.submitted_after)
+            && submitted(|time, before| time < before, self.submitted_before)
     }
 }
 
+/// Tasks of a listing together with the number of all matching tasks.
+pub(crate) struct TaskPage {
+    pub(crate) total: usize,
+    pub(crate) tasks: Vec<TaskInfo>,
+}
+
 pub enum Command {
     Status {
         id: Uuid,
         resp: Sender<Result<TaskStatus, AppError>>,
     },
+    /// Adds an asynchronous task with the given id. Responds with its id and initial state, or
+    /// with the id and state of the existing task a repeated request refers to.
     Generate {
         id: Uuid,
+        work: Work,
+        request: ProofRequest,
+        /// Result from the [`ProofCache`], the task is completed right away.
+        cached: Option<Arc<ProofResult>>,
+        resp: Sender<Result<(Uuid, TaskStatus), AppError>>,
+    },
+    /// Generates a proof without a task. Responds with the result.
+    Prove {
         config: Box<dyn ProofConfig>,
+        cacheable: bool,
+        resp: Sender<Result<Arc<ProofResult>, AppError>>,
     },
     Complete {
         id: Uuid,
-        zk_epd: Result<Arc<dyn ProofResponse>, ProvingError>,
+        zk_epd: Result<Arc<ProofResult>, ProvingError>,
+    },
+    /// Cancels an unfinished task or deletes a finished one. Responds with the state of a
+    /// cancelled task and `None` for a deleted task.
+    Cancel {
+        id: Uuid,
+        resp: Sender<Result<Option<TaskStatus>, AppError>>,
+    },
+    /// Responds with a receiver of the state changes of a task. The sender is dropped when the
+    /// task is deleted.
+    Subscribe {
+        id: Uuid,
+        resp: Sender<Result<watch::Receiver<TaskStatus>, AppError>>,
+    },
+    /// Responds with an overview of the tasks that were not evicted and match the query, in the
+    /// order they were submitted.
+    List {
+        query: TaskQuery,
+        resp: Sender<TaskPage>,
     },
 }
 
@@ -95,30 +256,705 @@ async fn next_cmd(
         .await
 }
 
-async fn proving_service(mut cmd_rx: UnboundedReceiver<Command>) {
-    let mut tasks: HashMap<Uuid, TaskStatus> = HashMap::new();
-    let mut join_set = JoinSet::new();
+/// Computation of a task.
+pub(crate) enum Work {
+    /// Proves a new zkEPD, with the proof cache if it is `cacheable`.
+    Prove {
+        config: Box<dyn ProofConfig>,
+        cacheable: bool,
+    },
+    /// Compresses the receipt of a verified zkEPD to Groth16.
+    Compress(Box<dyn VerifyConfig>),
+}
+
+/// Work waiting for or running on a worker.
+struct Job {
+    id: Uuid,
+    work: Work,
+    cancellation: Cancellation,
+}
+
+/// Generates at most `max_concurrency` proofs at the same time, further jobs wait in a queue.
+struct WorkerPool {
+    join_set: JoinSet<Command>,
+    running: usize,
+    queue: VecDeque<Job>,
+    max_concurrency: usize,
+    queue_capacity: usize,
+    dev_mode: bool,
+    cache: Option<Arc<dyn ProofCache>>,
+}
+
+impl WorkerPool {
+    fn new(
+        max_concurrency: usize,
+        queue_capacity: usize,
+        dev_mode: bool,
+        cache: Option<Arc<dyn ProofCache>>,
+    ) -> Self {
+        Self {
+            join_set: JoinSet::new(),
+            running: 0,
+            queue: VecDeque::new(),
+            max_concurrency: max_concurrency.max(1),
+            queue_capacity,
+            dev_mode,
+            cache,
+        }
+    }
+
+    /// `true` if a new job can neither be started nor queued.
+    fn is_full(&self) -> bool {
+        self.running >= self.max_concurrency && self.queue.len() >= self.queue_capacity
+    }
+
+    /// Starts the job if a worker is free, otherwise it is queued.
+    fn submit(&mut self, job: Job) -> TaskStatus {
+        if self.running < self.max_concurrency {
+            self.spawn(job);
+            TaskStatus::InProgress
+        } else {
+            self.queue.push_back(job);
+            TaskStatus::Queued(self.queue.len())
+        }
+    }
+
+    /// Frees the worker of a finished job and starts the next queued job, if any.
+    fn finish(&mut self) -> Option<Uuid> {
+        self.running -= 1;
+        let job = self.queue.pop_front()?;
+        let id = job.id;
+        self.spawn(job);
+        Some(id)
+    }
+
+    fn remove(&mut self, id: Uuid) {
+        self.queue.retain(|job| job.id != id);
+    }
+
+    fn spawn(
+        &mut self,
+        Job {
+            id,
+            work,
+            cancellation,
+        }: Job,
+    ) {
+        self.running += 1;
+        let dev_mode = self.dev_mode;
+        let cache = self.cache.clone();
+        self.join_set.spawn_blocking(move || {
+            let zk_epd = match work {
+                Work::Prove { config, cacheable } => {
+                    let cache = cache.as_deref().filter(|_| cacheable);
+                    generate_epd(config, &cancellation, dev_mode, cache)
+                }
+                Work::Compress(config) => compress_epd(&*config, &cancellation, dev_mode),
+            };
+            Command::Complete { id, zk_epd }
+        });
+    }
+}
+
+struct Task {
+    /// Current state, subscribers are notified of its changes.
+    status: watch::Sender<TaskStatus>,
+    /// `zktype` of the request, `None` if the stored request could not be read.
+    guest: Option<Box<str>>,
+    /// Whether a SNARK is requested, `None` if the stored request could not be read.
+    snark: Option<bool>,
+    /// [`ProofRequest::fingerprint`] of the request.
+    fingerprint: Option<[u8; 32]>,
+    idempotency_key: Option<Box<str>>,
+    /// URL the task is posted to when it is finished.
+    callback: Option<Box<str>>,
+    /// `true` if the salt of the result is posted to the callback.
+    callback_salt: bool,
+    /// Seconds since the Unix epoch, `None` if the task store did not record it.
+    submitted_at: Option<u64>,
+    /// Seconds since the Unix epoch when a worker started the task, only kept in memory.
+    started_at: Option<u64>,
+    /// Seconds since the Unix epoch, `None` while the task is not finished.
+    completed_at: Option<u64>,
+    /// Bytes of the stored result.
+    size: usize,
+    cancellation: Cancellation,
+}
+
+impl Task {
+    fn new(
+        id: Uuid,
+        status: TaskStatus,
+        submitted_at: Option<u64>,
+        completed_at: Option<u64>,
+    ) -> Self {
+        Self {
+            status: watch::Sender::new(status),
+            guest: None,
+            snark: None,
+            fingerprint: None,
+            idempotency_key: None,
+            callback: None,
+            callback_salt: false,
+            submitted_at,
+            started_at: None,
+            completed_at,
+            size: 0,
+            cancellation: Cancellation::new(id),
+        }
+    }
+
+    /// Sets the guest, SNARK flag, fingerprint, idempotency key and callback of the `request` the
+    /// task was created from.
+    fn with_request(mut self, request: Option<&ProofRequest>) -> Self {
+        self.guest = request.map(|request| request.zk_type().into());
+        self.snark = request.map(ProofRequest::snark_p);
+        self.fingerprint = request.map(ProofRequest::fingerprint);
+        self.idempotency_key =
+            request.and_then(|request| request.idempotency_key().map(Into::into));
+        self.callback = request.and_then(|request| request.callback().map(Into::into));
+        self.callback_salt = request.is_some_and(ProofRequest::callback_salt);
+        self
+    }
+
+    fn summary(&self) -> TaskSummary {
+        TaskSummary {
+            zk_type: self.guest.clone(),
+            snark: self.snark,
+            fingerprint: self.fingerprint.map(hex::encode),
+            idempotency_key: self.idempotency_key.clone(),
+        }
+    }
+
+    /// Sets what the `summary` of a finished task kept of its request.
+    fn with_summary(mut self, summary: TaskSummary) -> Self {
+        self.guest = summary.zk_type;
+        self.snark = summary.snark;
+        self.fingerprint = summary
+            .fingerprint
+            .and_then(|fingerprint| <[u8; 32]>::from_hex(fingerprint).ok());
+        self.idempotency_key = summary.idempotency_key;
+        self
+    }
+
+    /// Sets the status of a finished task and posts it to the callback.
+    fn finish(&mut self, id: Uuid, status: TaskStatus, completed_at: u64, webhooks: &Webhooks) {
+        if let Some(callback) = &self.callback {
+            webhooks.notify(callback, id, &status, self.callback_salt);
+        }
+        self.status.send_replace(status);
+        self.completed_at = Some(completed_at);
+    }
+}
+
+/// Tasks of the proving service, written through to the [`TaskStore`].
+struct Tasks {
+    tasks: HashMap<Uuid, Task>,
+    store: Box<dyn TaskStore>,
+    /// How long the results of finished tasks are kept, `None` to keep them forever.
+    retention: Option<Duration>,
+    /// Maximal number of finished tasks that are kept, `None` for no limit.
+    max_finished: Option<usize>,
+    /// Completion times of the evicted tasks by their id.
+    expired: HashMap<Uuid, u64>,
+    /// Tasks by the idempotency key of their request.
+    keys: HashMap<Box<str>, Uuid>,
+    webhooks: Webhooks,
+}
+
+impl Tasks {
+    fn get(&self, id: Uuid) -> Option<TaskStatus> {
+        match self.tasks.get(&id) {
+            Some(task) => Some(task.status.borrow().clone()),
+            None => self
+                .expired
+                .contains_key(&id)
+                .then_some(TaskStatus::Expired),
+        }
+    }
+
+    fn insert(&mut self, id: Uuid, task: Task) {
+        if let Some(key) = &task.idempotency_key {
+            self.keys.insert(key.clone(), id);
+        }
+        self.tasks.insert(id, task);
+    }
+
+    fn remove(&mut self, id: Uuid) -> Option<Task> {
+        let task = self.tasks.remove(&id)?;
+        if let Some(key) = &task.idempotency_key {
+            self.keys.remove(key);
+        }
+        Some(task)
+    }
+
+    /// Existing task a repeated `request` refers to: the task with the same idempotency key, or
+    /// if the request allows reuse a task with the same content that did not fail.
+    fn find(&self, request: &ProofRequest) -> Result<Option<Uuid>, AppError> {
+        let fingerprint = Some(request.fingerprint());
+        if let Some(&id) = request.idempotency_key().and_then(|key| self.keys.get(key)) {
+            return match self.tasks[&id].fingerprint == fingerprint {
+                true => Ok(Some(id)),
+                false => Err(AppError::IdempotencyKeyReused),
+            };
+        }
+        if !request.reuse() {
+            return Ok(None);
+        }
+        let reusable = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.fingerprint == fingerprint)
+            .filter(|(_, task)| {
+                !matches!(
+                    *task.status.borrow(),
+                    TaskStatus::Cancelled | TaskStatus::Failed(_)
+                )
+            })
+            .max_by_key(|(_, task)| task.submitted_at);
+        Ok(reusable.map(|(&id, _)| id))
+    }
+
+    /// Adds a task and returns the cancellation of its generation.
+    fn submit(&mut self, id: Uuid, request: &ProofRequest) -> Cancellation {
+        let submitted_at = unix_time(SystemTime::now());
+        let task = Task::new(id, TaskStatus::Submitted, Some(submitted_at), None)
+            .with_request(Some(request));
+        let cancellation = task.cancellation.clone();
+        self.insert(id, task);
+        let request = serde_json::to_string(request).expect("Request could not be serialized!");
+        if let Err(err) = self.store.insert(id, &request, submitted_at) {
+            tracing::error!("Task {id} could not be stored: {err:#}");
+        }
+        cancellation
+    }
+
+    fn set_status(&mut self, id: Uuid, status: TaskStatus) {
+        if let Some(task) = self.tasks.get_mut(&id) {
+            if let TaskStatus::InProgress = status {
+                task.started_at = Some(unix_time(SystemTime::now()));
+            }
+            task.status.send_replace(status);
+        }
+    }
+
+    /// Updates the positions of the queued tasks after the queue moved.
+    fn update_positions(&mut self, pool: &WorkerPool) {
+        for (index, job) in pool.queue.iter().enumerate() {
+            let Some(task) = self.tasks.get(&job.id) else {
+                continue;
+            };
+            task.status.send_if_modified(|status| match status {
+                TaskStatus::Queued(position) if *position != index + 1 => {
+                    *position = index + 1;
+                    true
+                }
+                _ => false,
+            });
+        }
+    }
+
+    fn subscribe(&self, id: Uuid) -> Option<watch::Receiver<TaskStatus>> {
+        match self.tasks.get(&id) {
+            Some(task) => Some(task.status.subscribe()),
+            // the closed channel only yields the final state
+            None => self
+                .expired
+                .contains_key(&id)
+                .then(|| watch::channel(TaskStatus::Expired).1),
+        }
+    }
+
+    /// Stores the result of a task and returns its size.
+    fn store_result(&mut self, id: Uuid, result: &StoredResult, completed_at: u64) -> usize {
+        let result = serde_json::to_string(result).expect("Result could not be serialized!");
+        self.store_completion(id, &result, completed_at);
+        result.len()
+    }
+
+    /// Stores the result of a finished task, its request is replaced by the summary.
+    fn store_completion(&mut self, id: Uuid, result: &str, completed_at: u64) {
+        let summary = self.tasks.get(&id).map(Task::summary).unwrap_or_default();
+        let summary = serde_json::to_string(&summary).expect("Summary could not be serialized!");
+        if let Err(err) = self.store.complete(id, &summary, result, completed_at) {
+            tracing::error!("Result of task {id} could not be stored: {err:#}");
+        }
+    }
+
+    fn complete(&mut self, id: Uuid, zk_epd: Result<Arc<ProofResult>, ProvingError>) {
+        // the result of a cancelled or deleted task is discarded
+        let guest = match self.tasks.get(&id) {
+            Some(task) if !task.status.borrow().is_finished() => task.guest.clone(),
+            _ => return,
+        };
+        let completed_at = unix_time(SystemTime::now());
+        let (stored, status) = match zk_epd {
+            Ok(result) => (
+                StoredResult::Complete(result.clone()),
+                TaskStatus::Complete(result),
+            ),
+            Err(err) => {
+                tracing::warn!("Task {id} failed: {err}");
+                let error = TaskError::new(&err, guest);
+                (
+                    StoredResult::Failed(error.clone()),
+                    TaskStatus::Failed(error),
+                )
+            }
+        };
+        let size = self.store_result(id, &stored, completed_at);
+        if let Some(task) = self.tasks.get_mut(&id) {
+            task.finish(id, status, completed_at, &self.webhooks);
+            task.size = size;
+        }
+    }
+
+    fn cancel(&mut self, id: Uuid) -> Result<Option<TaskStatus>, AppError> {
+        if self.expired.remove(&id).is_some() {
+            if let Err(err) = self.store.remove(id) {
+                tracing::warn!("Task {id} could not be removed: {err:#}");
+            }
+            return Ok(None);
+        }
+        let task = self.tasks.get_mut(&id).ok_or(AppError::UnknownTask(id))?;
+        if task.status.borrow().is_finished() {
+            self.remove(id);
+            if let Err(err) = self.store.remove(id) {
+                tracing::warn!("Task {id} could not be removed: {err:#}");
+            }
+            return Ok(None);
+        }
+        task.cancellation.cancel();
+        let completed_at = unix_time(SystemTime::now());
+        task.finish(id, TaskStatus::Cancelled, completed_at, &self.webhooks);
+        let size = self.store_result(id, &StoredResult::Cancelled, completed_at);
+        if let Some(task) = self.tasks.get_mut(&id) {
+            task.size = size;
+        }
+        Ok(Some(TaskStatus::Cancelled))
+    }
+
+    /// Evicts the tasks finished longer than the retention period ago and the oldest finished
+    /// tasks exceeding the maximal number. Evicted tasks are reported as expired for another
+    /// retention period and up to the same maximal number, then they are forgotten.
+    fn evict(&mut self) {
+        if self.retention.is_none() && self.max_finished.is_none() {
+            return;
+        }
+        let now = SystemTime::now();
+        let expiry = |periods: u32| {
+            self.retention
+                .map(|retention| now.checked_sub(retention * periods).map_or(0, unix_time))
+        };
+        let (expiry, forget_before) = (expiry(1), expiry(2));
+
+        let finished = self
+            .tasks
+            .iter()
+            .filter_map(|(&id, task)| task.completed_at.map(|time| (time, id)))
+            .collect();
+        for id in outdated(finished, expiry, self.max_finished) {
+            self.expire(id);
+        }
+        let expired = self.expired.iter().map(|(&id, &time)| (time, id)).collect();
+        for id in outdated(expired, forget_before, self.max_finished) {
+            self.expired.remove(&id);
+            if let Err(err) = self.store.remove(id) {
+                tracing::warn!("Task {id} could not be removed: {err:#}");
+            }
+        }
+    }
+
+    /// Replaces a finished task by a record that it expired. Subscribers receive no further
+    /// state changes.
+    fn expire(&mut self, id: Uuid) {
+        let Some(task) = self.remove(id) else {
+            return;
+        };
+        let completed_at = task.completed_at.unwrap_or_default();
+        self.store_result(id, &StoredResult::Expired, completed_at);
+        self.expired.insert(id, completed_at);
+    }
+
+    fn list(&self, query: &TaskQuery) -> TaskPage {
+        let mut tasks: Vec<_> = self
+            .tasks
+            .iter()
+            .map(|(&id, task)| TaskInfo {
+                id,
+                state: task.status.borrow().clone(),
+                zk_type: task.guest.clone(),
+                snark: task.snark,
+                submitted_at: task.submitted_at,
+                started_at: task.started_at,
+                completed_at: task.completed_at,
+                size: task.size,
+            })
+            .filter(|task| query.matches(task))
+            .collect();
+        tasks.sort_by_key(|task| (task.submitted_at, task.id));
+        let total = tasks.len();
+        let tasks = tasks
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        TaskPage { total, tasks }
+    }
+
+    /// Restores the stored tasks and returns the tasks that have to be resumed.
+    fn restore(
+        &mut self,
+        config_map: &ConfigFactoryMap,
+        issuer_key: &Option<Arc<IssuerKey>>,
+    ) -> Result<Vec<Job>> {
+        let mut resumed = Vec::new();
+        for StoredTask {
+            id,
+            request,
+            result,
+            submitted_at,
+            completed_at,
+        } in self.store.load()?
+        {
+            match result {
+                Some(result) => {
+                    let status = match serde_json::from_str(&result)? {
+                        StoredResult::Complete(result) => TaskStatus::Complete(result),
+                        StoredResult::Failed(error) => TaskStatus::Failed(error),
+                        StoredResult::Cancelled => TaskStatus::Cancelled,
+                        StoredResult::Expired => {
+                            self.expired.insert(id, completed_at.unwrap_or_default());
+                            continue;
+                        }
+                    };
+                    let task = Task::new(id, status, submitted_at, completed_at);
+                    let full_request = serde_json::from_str::<ProofRequest>(&request).ok();
+                    let mut task = match &full_request {
+                        Some(request) => task.with_request(Some(request)),
+                        None => {
+                            task.with_summary(serde_json::from_str(&request).unwrap_or_default())
+                        }
+                    };
+                    task.size = result.len();
+                    self.insert(id, task);
+                    // stored before finished tasks only kept a summary of their request
+                    if full_request.is_some() {
+                        self.store_completion(id, &result, completed_at.unwrap_or_default());
+                    }
+                }
+                None => {
+                    let request = serde_json::from_str::<ProofRequest>(&request);
+                    let task = Task::new(id, TaskStatus::Submitted, submitted_at, None)
+                        .with_request(request.as_ref().ok());
+                    let work = request.map_err(anyhow::Error::from).and_then(|request| {
+                        request
+                            .build_work(config_map, issuer_key.clone())
+                            .map_err(anyhow::Error::from)
+                    });
+                    let cancellation = task.cancellation.clone();
+                    self.insert(id, task);
+                    match work {
+                        Ok(work) => resumed.push(Job {
+                            id,
+                            work,
+                            cancellation,
+                        }),
+                        Err(err) => {
+                            self.complete(id, Err(err.context("Task could not be resumed").into()))
+                        }
+                    }
+                }
+            }
+        }
+        self.evict();
+        Ok(resumed)
+    }
+}
+
+/// Ids of the `(completion time, id)` entries completed before `expiry` and of the oldest entries
+/// exceeding `max`.
+fn outdated(mut entries: Vec<(u64, Uuid)>, expiry: Option<u64>, max: Option<usize>) -> Vec<Uuid> {
+    let mut outdated = Vec::new();
+    if let Some(expiry) = expiry {
+        entries.retain(|&(time, id)| {
+            if time < expiry {
+                outdated.push(id);
+            }
+            time >= expiry
+        });
+    }
+    if let Some(excess) = max.and_then(|max| entries.len().checked_sub(max)) {
+        entries.sort_unstable();
+        outdated.extend(entries[..excess].iter().map(|&(_, id)| id));
+    }
+    outdated
+}
+
+async fn proving_service(
+    mut cmd_rx: UnboundedReceiver<Command>,
+    mut tasks: Tasks,
+    mut pool: WorkerPool,
+    resumed: Vec<Job>,
+) {
+    for job in resumed {
+        tracing::info!("Resuming task {}", job.id);
+        let id = job.id;
+        let status = pool.submit(job);
+        tasks.set_status(id, status);
+    }
+    // proofs without a task, waiting for their result
+    let mut waiting = HashMap::new();
+    // finished tasks also expire while no commands arrive
+    let mut eviction = tokio::time::interval(EVICTION_INTERVAL);
+    eviction.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-    while let Some(cmd) = next_cmd(&mut cmd_rx, &mut join_set).await {
+    loop {
+        let cmd = tokio::select! {
+            cmd = next_cmd(&mut cmd_rx, &mut pool.join_set) => cmd,
+            _ = eviction.tick() => {
+                tasks.evict();
+                continue;
+            }
+        };
+        let Some(cmd) = cmd else {
+            break;
+        };
+        tasks.evict();
         match cmd {
             Command::Status { id, resp } => {
-                let result = tasks.get(&id).ok_or(AppError::UnknownTask(id)).cloned();
-                let _ = resp.send(result);
+                let _ = resp.send(tasks.get(id).ok_or(AppError::UnknownTask(id)));
             }
-            Command::Generate { id, config } => {
-                tasks.insert(id, TaskStatus::InProgress);
-                join_set.spawn_blocking(move || {
-                    let zk_epd = generate_epd(config);
-                    Command::Complete { id, zk_epd }
+            Command::Generate {
+                id,
+                work,
+                request,
+                cached,
+                resp,
+            } => {
+                match tasks.find(&request) {
+                    Ok(Some(existing)) => {
+                        let status = tasks.get(existing).expect("Found task exists!");
+                        let _ = resp.send(Ok((existing, status)));
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        let _ = resp.send(Err(err));
+                        continue;
+                    }
+                }
+                if let Some(result) = cached {
+                    tasks.submit(id, &request);
+                    tasks.complete(id, Ok(result));
+                    let status = tasks.get(id).expect("Completed task exists!");
+                    let _ = resp.send(Ok((id, status)));
+                    continue;
+                }
+                if pool.is_full() {
+                    let _ = resp.send(Err(AppError::QueueFull));
+                    continue;
+                }
+                let cancellation = tasks.submit(id, &request);
+                let status = pool.submit(Job {
+                    id,
+                    work,
+                    cancellation,
                 });
+                tasks.set_status(id, status.clone());
+                let _ = resp.send(Ok((id, status)));
             }
-            Command::Complete { id, zk_epd } => {
-                let task = tasks.get_mut(&id).unwrap_or_else(|| {
-                    panic!("Failure while adding EPD to unknown Task. Id: {id}")
+            Command::Prove {
+                config,
+                cacheable,
+                resp,
+            } => {
+                if pool.is_full() {
+                    let _ = resp.send(Err(AppError::QueueFull));
+                    continue;
+                }
+                let id = Uuid::new_v4();
+                waiting.insert(id, resp);
+                pool.submit(Job {
+                    id,
+                    work: Work::Prove { config, cacheable },
+                    cancellation: Cancellation::new(id),
                 });
-                let zk_epd = zk_epd.map_err(Into::into);
-                *task = TaskStatus::Complete(zk_epd);
             }
+            Command::Complete { id, zk_epd } => {
+                if let Some(started) = pool.finish() {
+                    tasks.set_status(started, TaskStatus::InProgress);
+                    tasks.update_positions(&pool);
+                }
+                match waiting.remove(&id) {
+                    Some(resp) => {
+                        let _ = resp.send(zk_epd.map_err(Into::into));
+                    }
+                    None => tasks.complete(id, zk_epd),
+                }
+            }
+            Command::Cancel { id, resp } => {
+                pool.remove(id);
+                tasks.update_positions(&pool);
+                let _ = resp.send(tasks.cancel(id));
+            }
+            Command::Subscribe { id, resp } => {
+                let _ = resp.send(tasks.subscribe(id).ok_or(AppError::UnknownTask(id)));
+            }
+            Command::List { query, resp } => {
+                let _ = resp.send(tasks.list(&query));
+            }
+        }
+    }
+}
+
+/// Settings of the proving service.
+pub struct ProvingOptions {
+    /// Persists the asynchronous tasks.
+    pub task_store: Box<dyn TaskStore>,
+    /// How long the results of finished tasks are kept, `None` to keep them forever. Evicted
+    /// tasks report the `Expired` state for another retention period.
+    pub retention: Option<Duration>,
+    /// Maximal number of finished tasks that are kept, the oldest are evicted first. `None` for
+    /// no limit. At most as many evicted tasks report the `Expired` state.
+    pub max_finished: Option<usize>,
+    /// Bearer token of the admin endpoints, they are disabled without a token.
+    pub admin_token: Option<Box<str>>,
+    /// Maximal number of proofs generated at the same time.
+    pub max_concurrency: usize,
+    /// Maximal number of proofs waiting for a free worker. Further requests are rejected with
+    /// `503 Service Unavailable`.
+    pub queue_capacity: usize,
+    /// Callbacks of finished tasks.
+    pub webhooks: WebhookOptions,
+    /// Only executes the guests and returns fake receipts instead of proofs, for fast tests.
+    /// Fake receipts are rejected by `/verify` unless the service runs in dev mode.
+    pub dev_mode: bool,
+    /// Proofs of identical inputs with an explicit salt are served from the cache instead of being
+    /// proven again, `None` to always prove. Proofs of guests that are not in use are removed.
+    pub proof_cache: Option<Arc<dyn ProofCache>>,
+    /// Cycles the prover proves per second, only for the `estimated_proving_ms` of a preflight.
+    /// The default is a rough guess for a CPU prover, measure the prover to set it.
+    pub proving_cycles_per_second: u64,
+    /// Milliseconds the Groth16 compression of a proof takes, only for the `estimated_proving_ms`
+    /// of a preflight. The default is a rough guess for a CPU prover.
+    pub groth16_proving_ms: u64,
+}
+
+impl Default for ProvingOptions {
+    fn default() -> Self {
+        Self {
+            task_store: Box::new(VolatileTaskStore),
+            retention: None,
+            max_finished: None,
+            admin_token: None,
+            max_concurrency: 1,
+            queue_capacity: 16,
+            webhooks: WebhookOptions::default(),
+            dev_mode: false,
+            proof_cache: None,
+            proving_cycles_per_second: 100_000,
+            groth16_proving_ms: 60_000,
         }
     }
 }
@@ -126,25 +962,174 @@ async fn proving_service(mut cmd_rx: UnboundedReceiver<Command>) {
 #[derive(Clone)]
 pub struct ProvingService {
     tx: UnboundedSender<Command>,
+    dev_mode: bool,
+    cache: Option<Arc<dyn ProofCache>>,
+    webhooks: Webhooks,
+    /// Preflights that are running or waiting, limited like the proofs of the [`WorkerPool`].
+    preflights_admitted: Arc<Semaphore>,
+    /// Preflights that execute the guest.
+    preflights_running: Arc<Semaphore>,
+    estimate: ProvingEstimate,
 }
 
 impl ProvingService {
-    pub fn new() -> (ProvingService, JoinHandle<()>) {
+    /// Starts the proving service and resumes the unfinished tasks of the `store`.
+    pub(crate) fn new(
+        options: ProvingOptions,
+        config_map: &ConfigFactoryMap,
+        issuer_key: &Option<Arc<IssuerKey>>,
+    ) -> (ProvingService, JoinHandle<()>) {
         let (tx, rx) = mpsc::unbounded_channel();
+        let webhooks = Webhooks::new(options.webhooks);
+        let mut tasks = Tasks {
+            tasks: HashMap::new(),
+            store: options.task_store,
+            retention: options.retention,
+            max_finished: options.max_finished,
+            expired: HashMap::new(),
+            keys: HashMap::new(),
+            webhooks: webhooks.clone(),
+        };
+        if let Some(cache) = &options.proof_cache {
+            if let Err(err) = cache.prune(&config_map.image_ids()) {
+                tracing::warn!("Proof cache could not be pruned: {err:#}");
+            }
+        }
+        let workers = options.max_concurrency.max(1);
+        let pool = WorkerPool::new(
+            options.max_concurrency,
+            options.queue_capacity,
+            options.dev_mode,
+            options.proof_cache.clone(),
+        );
+        let resumed = tasks
+            .restore(config_map, issuer_key)
+            .expect("Tasks could not be restored from the task store!");
         let proving_task = tokio::spawn(async {
-            proving_service(rx).await;
+            proving_service(rx, tasks, pool, resumed).await;
         });
-        (Self { tx }, proving_task)
+        let service = Self {
+            tx,
+            dev_mode: options.dev_mode,
+            cache: options.proof_cache,
+            webhooks,
+            preflights_admitted: Arc::new(Semaphore::new(workers + options.queue_capacity)),
+            preflights_running: Arc::new(Semaphore::new(workers)),
+            estimate: ProvingEstimate {
+                cycles_per_second: options.proving_cycles_per_second.max(1),
+                groth16_ms: options.groth16_proving_ms,
+            },
+        };
+        (service, proving_task)
     }
 
-    #[tracing::instrument(skip(self))]
-    pub fn add_task(&self, config: Box<dyn ProofConfig>) -> Uuid {
+    /// Executes the guest of the `config` without proving. Like proofs, at most `max_concurrency`
+    /// preflights run at the same time and `queue_capacity` wait, further ones are rejected.
+    pub(crate) async fn preflight(
+        &self,
+        config: Box<dyn ProofConfig>,
+    ) -> Result<Preflight, AppError> {
+        let admitted = self
+            .preflights_admitted
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| AppError::QueueFull)?;
+        let running = self
+            .preflights_running
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Preflight semaphore is never closed!");
+        // the permits are held until the execution ends, even if the request is dropped before
+        let estimate = self.estimate;
+        let preflight = tokio::task::spawn_blocking(move || {
+            let _permits = (admitted, running);
+            preflight(&*config, estimate)
+        })
+        .await
+        .expect("Preflight task failed unexpectedly")?;
+        Ok(preflight)
+    }
+
+    /// Delivers the callbacks of the tasks.
+    pub(crate) fn webhooks(&self) -> &Webhooks {
+        &self.webhooks
+    }
+
+    /// Result of the `config` from the proof cache, looked up without blocking the runtime.
+    async fn lookup_cache(
+        &self,
+        config: Box<dyn ProofConfig>,
+    ) -> (Box<dyn ProofConfig>, Option<Arc<ProofResult>>) {
+        let Some(cache) = self.cache.clone() else {
+            return (config, None);
+        };
+        let dev_mode = self.dev_mode;
+        tokio::task::spawn_blocking(move || {
+            let cached = cached_epd(&*config, &*cache, dev_mode);
+            (config, cached)
+        })
+        .await
+        .expect("Proof cache lookup failed unexpectedly")
+    }
+
+    /// Adds an asynchronous task and returns its id and initial state.
+    #[tracing::instrument(skip_all, fields(zk_type = request.zk_type()))]
+    pub(crate) async fn add_task(
+        &self,
+        work: Work,
+        request: ProofRequest,
+    ) -> Result<(Uuid, TaskStatus), AppError> {
+        let (work, cached) = match work {
+            Work::Prove {
+                config,
+                cacheable: true,
+            } => {
+                let (config, cached) = self.lookup_cache(config).await;
+                let cacheable = true;
+                (Work::Prove { config, cacheable }, cached)
+            }
+            work => (work, None),
+        };
         let id = Uuid::new_v4();
-        let cmd = Command::Generate { id, config };
+        let (resp, rx) = oneshot::channel();
+        let cmd = Command::Generate {
+            id,
+            work,
+            request,
+            cached,
+            resp,
+        };
+        self.tx
+            .send(cmd)
+            .expect("Command channel was irregularly closed");
+        rx.await.expect("Response Channel was close unexpectedly")
+    }
+
+    /// Generates a proof on a worker of the service and waits for the result.
+    #[tracing::instrument(skip(self, config))]
+    pub(crate) async fn prove(
+        &self,
+        config: Box<dyn ProofConfig>,
+        cacheable: bool,
+    ) -> Result<Arc<ProofResult>, AppError> {
+        let config = match cacheable {
+            true => match self.lookup_cache(config).await {
+                (_, Some(result)) => return Ok(result),
+                (config, None) => config,
+            },
+            false => config,
+        };
+        let (resp, rx) = oneshot::channel();
+        let cmd = Command::Prove {
+            config,
+            cacheable,
+            resp,
+        };
         self.tx
             .send(cmd)
             .expect("Command channel was irregularly closed");
-        id
+        rx.await.expect("Response Channel was close unexpectedly")
     }
 
     #[tracing::instrument(skip(self))]
@@ -156,51 +1141,324 @@ impl ProvingService {
             .expect("Command channel was irregularly closed");
         rx.await.expect("Response Channel was close unexpectedly")
     }
+
+    /// Cancels an unfinished task or deletes a finished one, see [`Command::Cancel`].
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn cancel_task(&self, id: Uuid) -> Result<Option<TaskStatus>, AppError> {
+        let (resp, rx) = oneshot::channel();
+        let cmd = Command::Cancel { id, resp };
+        self.tx
+            .send(cmd)
+            .expect("Command channel was irregularly closed");
+        rx.await.expect("Response Channel was close unexpectedly")
+    }
+
+    /// Receiver of the state changes of a task, see [`Command::Subscribe`].
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn subscribe(
+        &self,
+        id: Uuid,
+    ) -> Result<watch::Receiver<TaskStatus>, AppError> {
+        let (resp, rx) = oneshot::channel();
+        let cmd = Command::Subscribe { id, resp };
+        self.tx
+            .send(cmd)
+            .expect("Command channel was irregularly closed");
+        rx.await.expect("Response Channel was close unexpectedly")
+    }
+
+    /// Overview of the tasks matching the `query`, see [`Command::List`].
+    #[tracing::instrument(skip(self, query))]
+    pub(crate) async fn list_tasks(&self, query: TaskQuery) -> TaskPage {
+        let (resp, rx) = oneshot::channel();
+        let cmd = Command::List { query, resp };
+        self.tx
+            .send(cmd)
+            .expect("Command channel was irregularly closed");
+        rx.await.expect("Response Channel was close unexpectedly")
+    }
 }
 
 trait WriteConfigExt {
     fn write_config(&mut self, config: &dyn ProofConfig) -> Result<&mut Self, Error>;
 }
 
-#[tracing::instrument]
-fn generate_proof(config: &dyn ProofConfig) -> Result<Receipt> {
+#[tracing::instrument(skip(config, cancellation))]
+fn generate_proof(
+    config: &dyn ProofConfig,
+    cancellation: &Cancellation,
+    dev_mode: bool,
+) -> Result<(Receipt, ProofStats)> {
     let env = ExecutorEnv::builder().write_config(config)?.build()?;
 
-    let prover = ExternalProver::new("ipc", "r0vm");
+    let prover = cancellation.prover()?;
 
-    let opts = if config.snark_p() {
-        ProverOpts::groth16()
-    } else {
-        ProverOpts::default()
-    };
+    let opts = prover_opts(config);
 
     // Proof information by proving the specified ELF binary.
     // This struct contains the receipt along with statistics about execution of the guest
     let start = Instant::now();
-    let prove_info = prover.prove_with_opts(env, config.guest_elf(), &opts)?;
+    let (receipt, stats) = if dev_mode {
+        execute(&prover, env, config.guest_elf())?
+    } else {
+        let prove_info = prover.prove_with_opts(env, config.guest_elf(), &opts)?;
+        (prove_info.receipt, prove_info.stats)
+    };
     let elapsed = start.elapsed();
 
-    println!(
-        "Created Proof in {} seconds. Stats:\nUser Cycles: {}, Total Cycles: {}, Segments: {}",
+    tracing::info!(
+        "Created {}Proof in {} seconds. Stats:\nUser Cycles: {}, Total Cycles: {}, Segments: {}",
+        if dev_mode { "fake " } else { "" },
         elapsed.as_secs(),
-        prove_info.stats.user_cycles,
-        prove_info.stats.total_cycles,
-        prove_info.stats.segments
+        stats.user_cycles,
+        stats.total_cycles,
+        stats.segments
     );
 
+    let stats = ProofStats {
+        user_cycles: stats.user_cycles,
+        total_cycles: stats.total_cycles,
+        segments: stats.segments,
+        elapsed_ms: elapsed.as_millis() as u64,
+        dev_mode,
+        cached: false,
+    };
+
     // Return proof receipt
-    Ok(prove_info.receipt)
+    Ok((receipt, stats))
+}
+
+/// Only executes the guest and returns a fake receipt of its journal, for the
+/// [`ProvingOptions::dev_mode`] and [`preflight`] checks.
+fn execute(
+    executor: &dyn Executor,
+    env: ExecutorEnv,
+    elf: &[u8],
+) -> Result<(Receipt, SessionStats)> {
+    let session = executor.execute(env, elf)?;
+    if session.exit_code != ExitCode::Halted(0) {
+        bail!("Guest exited with {:?}", session.exit_code);
+    }
+    let claim = ReceiptClaim::ok(compute_image_id(elf)?, session.journal.bytes.clone());
+    let stats = SessionStats {
+        segments: session.segments.len(),
+        total_cycles: session
+            .segments
+            .iter()
+            .map(|segment| 1u64 << segment.po2)
+            .sum(),
+        user_cycles: session.cycles(),
+    };
+    let receipt = Receipt::new(
+        InnerReceipt::Fake(FakeReceipt::new(claim)),
+        session.journal.bytes,
+    );
+    Ok((receipt, stats))
 }
 
-pub fn generate_epd(config: Box<dyn ProofConfig>) -> Result<Arc<dyn ProofResponse>, ProvingError> {
-    let receipt = generate_proof(&*config)?;
+fn prover_opts(config: &dyn ProofConfig) -> ProverOpts {
+    if config.snark_p() {
+        ProverOpts::groth16()
+    } else {
+        ProverOpts::default()
+    }
+}
+
+/// Proves the `config`, unless its proof is in the `cache`.
+pub(crate) fn generate_epd(
+    config: Box<dyn ProofConfig>,
+    cancellation: &Cancellation,
+    dev_mode: bool,
+    cache: Option<&dyn ProofCache>,
+) -> Result<Arc<ProofResult>, ProvingError> {
+    let cache = cache.and_then(|cache| Some((cache, cache_key(&*config, dev_mode)?)));
+    let cached = cache
+        .as_ref()
+        .and_then(|(cache, key)| cached_proof(*cache, key));
+    let (receipt, stats) = match cached {
+        Some(proof) => proof,
+        None => {
+            let proof = generate_proof(&*config, cancellation, dev_mode);
+            cancellation.finish();
+            let proof = proof.map_err(ProvingError::from_prover)?;
+            if let Some((cache, key)) = &cache {
+                cache_proof(*cache, key, &proof);
+            }
+            proof
+        }
+    };
+
+    let mut proof_result = config.decode_response(&receipt)?;
+    proof_result.stats = Some(stats);
+    Ok(Arc::new(proof_result))
+}
+
+/// Receipt and prover statistics in the [`ProofCache`].
+#[derive(Serialize, Deserialize)]
+struct CachedProof<R = Receipt> {
+    receipt: R,
+    stats: ProofStats,
+}
+
+/// Key of the proof of the `config` in the [`ProofCache`], `None` if the input could not be
+/// serialized.
+fn cache_key(config: &dyn ProofConfig, dev_mode: bool) -> Option<CacheKey> {
+    let key = || -> Result<CacheKey> {
+        let image_id = compute_image_id(config.guest_elf())?;
+        // everything the guest reads, in the order of `write_config`
+        let mut hasher = Sha256::new();
+        for receipt in config.assumptions() {
+            hasher.update(receipt.claim()?.digest());
+        }
+        let mut words = Vec::new();
+        for &image_id in config.assumption_ids() {
+            words.extend(risc0_zkvm::serde::to_vec(image_id)?);
+        }
+        words.extend(risc0_zkvm::serde::to_vec(config.salt())?);
+        if let Some(parameters) = config.parameters() {
+            words.extend(parameters.to_words()?);
+        }
+        words.extend(config.get_input().to_words()?);
+        for word in words {
+            hasher.update(word.to_le_bytes());
+        }
+        let receipt_kind = match (dev_mode, config.snark_p()) {
+            (true, _) => "Fake",
+            (false, true) => "Groth16",
+            (false, false) => "Composite",
+        };
+        Ok(CacheKey {
+            image_id: image_id.to_string().into(),
+            input_digest: hex::encode(hasher.finalize()).into(),
+            receipt_kind: receipt_kind.into(),
+        })
+    };
+    key()
+        .inspect_err(|err| tracing::warn!("Proof cache key could not be computed: {err:#}"))
+        .ok()
+}
 
-    let proof_response = config.decode_response(&receipt)?;
-    Ok(Arc::from(proof_response))
+/// The cached proof of the `key`, `None` on a miss or if the cache could not be read.
+fn cached_proof(cache: &dyn ProofCache, key: &CacheKey) -> Option<(Receipt, ProofStats)> {
+    let proof = cache
+        .get(key)
+        .inspect_err(|err| tracing::warn!("Proof cache could not be read: {err:#}"))
+        .ok()??;
+    let CachedProof { receipt, mut stats } = ciborium::de::from_reader(proof.as_slice())
+        .inspect_err(|err| tracing::warn!("Cached proof could not be decoded: {err}"))
+        .ok()?;
+    tracing::info!("Using cached proof {}", key.input_digest);
+    stats.cached = true;
+    Some((receipt, stats))
+}
+
+fn cache_proof(cache: &dyn ProofCache, key: &CacheKey, (receipt, stats): &(Receipt, ProofStats)) {
+    let mut proof = Vec::new();
+    let stats = *stats;
+    let stored = ciborium::ser::into_writer(&CachedProof { receipt, stats }, &mut proof)
+        .map_err(anyhow::Error::from)
+        .and_then(|()| cache.insert(key, &proof));
+    if let Err(err) = stored {
+        tracing::warn!("Proof could not be cached: {err:#}");
+    }
+}
+
+/// Result of the `config` if its proof is in the `cache`.
+fn cached_epd(
+    config: &dyn ProofConfig,
+    cache: &dyn ProofCache,
+    dev_mode: bool,
+) -> Option<Arc<ProofResult>> {
+    let (receipt, stats) = cached_proof(cache, &cache_key(config, dev_mode)?)?;
+    let mut proof_result = config
+        .decode_response(&receipt)
+        .inspect_err(|err| tracing::warn!("Cached proof could not be decoded: {err}"))
+        .ok()?;
+    proof_result.stats = Some(stats);
+    Some(Arc::new(proof_result))
+}
+
+/// Compresses the receipt of the verified zkEPD of the `config` to Groth16, without executing
+/// the guest again. Fake receipts are kept as they are in dev mode.
+pub(crate) fn compress_epd(
+    config: &dyn VerifyConfig,
+    cancellation: &Cancellation,
+    dev_mode: bool,
+) -> Result<Arc<ProofResult>, ProvingError> {
+    let receipt = config
+        .receipt()
+        .map_err(|err| ProvingError::ProvingFailed(err.into()))?;
+    let start = Instant::now();
+    let receipt = match &receipt.inner {
+        InnerReceipt::Fake(_) if dev_mode => receipt,
+        _ => {
+            let compressed = cancellation
+                .prover()
+                .and_then(|prover| prover.compress(&ProverOpts::groth16(), &receipt));
+            cancellation.finish();
+            compressed.map_err(ProvingError::from_prover)?
+        }
+    };
+    tracing::info!("Compressed proof in {} seconds", start.elapsed().as_secs());
+    Ok(Arc::new(ProofResult {
+        zk_epd: config.with_receipt(&receipt)?,
+        salt: None,
+        stats: None,
+    }))
+}
+
+/// Speed of the prover for the estimated proving time of a preflight, see [`ProvingOptions`].
+#[derive(Clone, Copy)]
+struct ProvingEstimate {
+    cycles_per_second: u64,
+    groth16_ms: u64,
+}
+
+/// Outcome of a [`preflight`] check.
+#[derive(Serialize)]
+pub(crate) struct Preflight {
+    /// The public EPD the proof would commit to.
+    epd: Value,
+    user_cycles: u64,
+    total_cycles: u64,
+    segments: usize,
+    /// Rough estimate of the proving time, from the configured speed of the prover.
+    estimated_proving_ms: u64,
+}
+
+/// Executes the guest without proving, e.g. to check the input before a proof is requested.
+fn preflight(
+    config: &dyn ProofConfig,
+    estimate: ProvingEstimate,
+) -> Result<Preflight, ProvingError> {
+    let env = ExecutorEnv::builder().write_config(config)?.build()?;
+    let (receipt, stats) = execute(&*default_executor(), env, config.guest_elf())
+        .map_err(ProvingError::from_prover)?;
+    let mut estimated_proving_ms = stats.total_cycles * 1000 / estimate.cycles_per_second;
+    if config.snark_p() {
+        estimated_proving_ms += estimate.groth16_ms;
+    }
+    Ok(Preflight {
+        epd: config.decode_epd(&receipt.journal)?,
+        user_cycles: stats.user_cycles,
+        total_cycles: stats.total_cycles,
+        segments: stats.segments,
+        estimated_proving_ms,
+    })
 }
 
 impl<'a> WriteConfigExt for ExecutorEnvBuilder<'a> {
     fn write_config<'b>(&'b mut self, config: &dyn ProofConfig) -> Result<&'b mut Self, Error> {
+        for receipt in config.assumptions() {
+            self.add_assumption(receipt.clone());
+        }
+        for &image_id in config.assumption_ids() {
+            self.write(image_id)?;
+        }
+        self.write(config.salt())?;
+        if let Some(parameters) = config.parameters() {
+            parameters.write_to_env(self)?;
+        }
         config.get_input().write_to_env(self)
     }
 }
@@ -210,6 +1468,9 @@ pub trait ProofInput {
         &self,
         env_builder: &'a mut ExecutorEnvBuilder<'b>,
     ) -> Result<&'a mut ExecutorEnvBuilder<'b>, Error>;
+
+    /// The words written to the guest, for the key of the [`ProofCache`].
+    fn to_words(&self) -> Result<Vec<u32>, Error>;
 }
 
 impl<T> ProofInput for T
@@ -222,21 +1483,36 @@ where
     ) -> Result<&'a mut ExecutorEnvBuilder<'b>, Error> {
         env_builder.write(self)
     }
+
+    fn to_words(&self) -> Result<Vec<u32>, Error> {
+        Ok(risc0_zkvm::serde::to_vec(self)?)
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn new_config<I, Epd, ZkEpd>(
     snark_p: bool,
     guest_elf: &'static [u8],
+    assumption_ids: &'static [&'static ImageId],
+    assumptions: Vec<Receipt>,
+    salt: Salt,
+    parameters: Option<Arc<dyn ProofInput + Send + Sync>>,
+    issuer: Option<Arc<IssuerKey>>,
     input: I,
 ) -> Box<dyn ProofConfig>
 where
     I: ProofInput + Sync + Send + Debug + 'static,
-    Epd: Sync + Send + Debug + DeserializeOwned + 'static,
-    ZkEpd: VerifiedEpd<Epd> + Send + Sync + Debug + Serialize + 'static
+    Epd: ComposedEpd + Sync + Send + Debug + Serialize + DeserializeOwned + 'static,
+    ZkEpd: VerifiedEpd<Epd> + Send + Sync + Debug + Serialize + 'static,
 {
     let config: ProofConfigImpl<I, Epd, ZkEpd> = ProofConfigImpl {
         snark_p,
         guest_elf,
+        assumption_ids,
+        assumptions,
+        salt,
+        parameters,
+        issuer,
         input,
         phantom_epd: Default::default(),
         phantom_zk_epd: Default::default(),
@@ -247,6 +1523,12 @@ where
 struct ProofConfigImpl<I, Epd, ZkEpd> {
     snark_p: bool,
     guest_elf: &'static [u8],
+    assumption_ids: &'static [&'static ImageId],
+    assumptions: Vec<Receipt>,
+    salt: Salt,
+    parameters: Option<Arc<dyn ProofInput + Send + Sync>>,
+    /// Issuer of the result credential, `None` if the zkEPD is returned as plain JSON.
+    issuer: Option<Arc<IssuerKey>>,
     input: I,
     phantom_epd: PhantomData<Epd>,
     phantom_zk_epd: PhantomData<ZkEpd>,
@@ -259,6 +1541,7 @@ where
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ProofConfig")
             .field("snark_p", &self.snark_p)
+            .field("assumptions", &self.assumptions.len())
             .field("input:", &self.input)
             .finish()
     }
@@ -267,8 +1550,8 @@ where
 impl<I, Epd, ZkEpd> ProofConfig for ProofConfigImpl<I, Epd, ZkEpd>
 where
     I: ProofInput + Send + Debug,
-    Epd: DeserializeOwned + Send + Debug,
-    ZkEpd: ProofResponse + VerifiedEpd<Epd> + Debug + Send + 'static
+    Epd: Serialize + DeserializeOwned + Send + Debug,
+    ZkEpd: VerifiedEpd<Epd> + Serialize + Debug + Send + Sync + 'static,
 {
     fn get_input(&self) -> &dyn ProofInput {
         &self.input
@@ -282,19 +1565,77 @@ where
         self.guest_elf
     }
 
-    fn decode_response(&self, receipt: &Receipt) -> Result<Box<dyn ProofResponse>, ProvingError> {
-        let epd: Epd = receipt.journal.decode()?;
+    fn assumption_ids(&self) -> &[&ImageId] {
+        self.assumption_ids
+    }
 
-        let mut receipt_cbor: Vec<u8> = Vec::new();
-        ciborium::ser::into_writer(&receipt, &mut receipt_cbor)?;
+    fn assumptions(&self) -> &[Receipt] {
+        &self.assumptions
+    }
+
+    fn salt(&self) -> &Salt {
+        &self.salt
+    }
+
+    fn parameters(&self) -> Option<&dyn ProofInput> {
+        self.parameters
+            .as_deref()
+            .map(|parameters| parameters as &dyn ProofInput)
+    }
+
+    fn decode_epd(&self, journal: &Journal) -> Result<Value, ProvingError> {
+        let epd: Epd = journal.decode()?;
+        Ok(serde_json::to_value(epd)?)
+    }
 
-        let zkp = BASE64_STANDARD.encode(&receipt_cbor).into_boxed_str();
+    fn decode_response(&self, receipt: &Receipt) -> Result<ProofResult, ProvingError> {
+        let epd: Epd = receipt.journal.decode()?;
+        let zkp = encode_receipt(receipt)?;
 
         let zk_epd = ZkEpd::from_result(epd, zkp);
-        Ok(Box::new(zk_epd))
+        let zk_epd = match &self.issuer {
+            Some(issuer) => issue_credential(issuer, &zk_epd)?,
+            None => serde_json::to_value(zk_epd)?,
+        };
+        Ok(ProofResult {
+            zk_epd,
+            salt: Some(self.salt),
+            stats: None,
+        })
     }
 }
 
+/// The `zkp` of a zkEPD, the base64 encoded CBOR of the receipt.
+pub(crate) fn encode_receipt(receipt: &Receipt) -> Result<Box<str>, ProvingError> {
+    let mut receipt_cbor: Vec<u8> = Vec::new();
+    ciborium::ser::into_writer(&receipt, &mut receipt_cbor)?;
+    Ok(BASE64_STANDARD.encode(&receipt_cbor).into_boxed_str())
+}
+
+/// Wraps a zkEPD into a signed DPP credential, presented in the shape of the DID mappings
+/// (`dpps`, `buildingPartDpps`) of the next proof request.
+///
+/// The subject is identified by a new `urn:uuid` that the next request references as DID.
+fn issue_credential<Epd, ZkEpd>(issuer: &IssuerKey, zk_epd: &ZkEpd) -> Result<Value, ProvingError>
+where
+    ZkEpd: VerifiedEpd<Epd> + Serialize,
+{
+    let did = format!("urn:uuid:{}", Uuid::new_v4());
+    let subject = match ZkEpd::SUBJECT_MEMBER {
+        Some(member) => json!({ "id": did, member: zk_epd }),
+        None => {
+            let mut subject = serde_json::to_value(zk_epd)?;
+            subject["id"] = did.as_str().into();
+            subject
+        }
+    };
+    let credential = issuer.issue(subject)?;
+    Ok(json!({
+        "did": did,
+        "dpp_vp": { "verifiableCredential": [credential] },
+    }))
+}
+
 pub trait ProofConfig: Debug + Send {
     fn get_input(&self) -> &dyn ProofInput;
 
@@ -302,18 +1643,406 @@ pub trait ProofConfig: Debug + Send {
 
     fn guest_elf(&self) -> &[u8];
 
-    fn decode_response(&self, receipt: &Receipt) -> Result<Box<dyn ProofResponse>, ProvingError>;
+    /// Image IDs of the composed guests, written to the guest before the input.
+    fn assumption_ids(&self) -> &[&ImageId];
+
+    /// Receipts the guest verifies with `env::verify`.
+    fn assumptions(&self) -> &[Receipt];
+
+    /// Salt of the input commitment, written to the guest before the input.
+    fn salt(&self) -> &Salt;
+
+    /// Public parameter dataset, written to the guest after the salt.
+    fn parameters(&self) -> Option<&dyn ProofInput>;
+
+    /// The public EPD committed to the `journal`.
+    fn decode_epd(&self, journal: &Journal) -> Result<Value, ProvingError>;
+
+    fn decode_response(&self, receipt: &Receipt) -> Result<ProofResult, ProvingError>;
 }
 
-pub trait ProofResponse: Send + Sync {
-    fn into_response(self: Arc<Self>) -> Response;
+/// Proven EPD together with the salt that opens its input commitment.
+///
+/// The salt is not part of the public EPD and is returned in the [`SALT_HEADER`] instead.
+#[derive(Serialize, Deserialize)]
+pub struct ProofResult {
+    /// The zkEPD, or the credential it is issued in.
+    zk_epd: Value,
+    /// `None` for compressed zkEPDs, their salt is only known to the client.
+    salt: Option<Salt>,
+    #[serde(default)]
+    stats: Option<ProofStats>,
 }
 
-impl<T> ProofResponse for T
-where
-    T: Serialize + Sync + Send,
-{
-    fn into_response(self: Arc<Self>) -> Response {
-        Json(self).into_response()
+impl ProofResult {
+    pub(crate) fn to_response(&self) -> Response {
+        let salt = self.salt.map(hex::encode);
+        let headers = salt.map(|salt| [(SALT_HEADER, salt)]);
+        (headers, Json(&self.zk_epd)).into_response()
+    }
+
+    pub(crate) fn zk_epd(&self) -> &Value {
+        &self.zk_epd
+    }
+
+    pub(crate) fn salt(&self) -> Option<&Salt> {
+        self.salt.as_ref()
+    }
+
+    pub(crate) fn stats(&self) -> Option<ProofStats> {
+        self.stats
+    }
+}
+
+/// Statistics of the prover for a generated proof.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) struct ProofStats {
+    user_cycles: u64,
+    total_cycles: u64,
+    segments: usize,
+    /// Duration of the proof generation in milliseconds.
+    elapsed_ms: u64,
+    /// `true` if the guest was only executed and the receipt is fake.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dev_mode: bool,
+    /// `true` if the proof was taken from the [`ProofCache`], the other statistics are those of
+    /// the original proof.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    cached: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::SqliteTaskStore;
+
+    fn new_tasks(store: Box<dyn TaskStore>) -> Tasks {
+        Tasks {
+            tasks: HashMap::new(),
+            store,
+            retention: None,
+            max_finished: None,
+            expired: HashMap::new(),
+            keys: HashMap::new(),
+            webhooks: Webhooks::new(WebhookOptions::default()),
+        }
+    }
+
+    fn finished_task(id: Uuid, completed_at: u64) -> Task {
+        Task::new(id, TaskStatus::Cancelled, Some(0), Some(completed_at))
+    }
+
+    #[tokio::test]
+    async fn evict_finished_tasks() {
+        let mut tasks = new_tasks(Box::new(SqliteTaskStore::in_memory().unwrap()));
+        tasks.retention = Some(Duration::from_secs(3600));
+        tasks.max_finished = Some(1);
+        let now = unix_time(SystemTime::now());
+        let (outdated, older, newer, running) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        for (id, task) in [
+            (outdated, finished_task(outdated, now - 7200)),
+            (older, finished_task(older, now - 10)),
+            (newer, finished_task(newer, now)),
+            (
+                running,
+                Task::new(running, TaskStatus::InProgress, Some(0), None),
+            ),
+        ] {
+            tasks.store.insert(id, "{}", 0).unwrap();
+            tasks.tasks.insert(id, task);
+        }
+        let subscriber = tasks.subscribe(older).unwrap();
+
+        tasks.evict();
+        // only the last evicted task is reported as expired, like the finished tasks
+        assert!(tasks.get(outdated).is_none());
+        assert!(matches!(tasks.get(older), Some(TaskStatus::Expired)));
+        assert!(matches!(tasks.get(newer), Some(TaskStatus::Cancelled)));
+        assert!(matches!(tasks.get(running), Some(TaskStatus::InProgress)));
+        assert!(tasks.get(Uuid::new_v4()).is_none());
+        assert!(subscriber.has_changed().is_err());
+        assert_eq!(tasks.list(&TaskQuery::default()).total, 2);
+
+        // evicted tasks stay expired after a restart
+        let mut restored = new_tasks(std::mem::replace(
+            &mut tasks.store,
+            Box::new(VolatileTaskStore),
+        ));
+        restored
+            .restore(&ConfigFactoryMap::new(Vec::new()), &None)
+            .unwrap();
+        assert!(matches!(restored.get(older), Some(TaskStatus::Expired)));
+        assert!(matches!(restored.cancel(older), Ok(None)));
+        assert!(restored.get(older).is_none());
+    }
+
+    #[tokio::test]
+    async fn forget_expired_tasks() {
+        let mut tasks = new_tasks(Box::new(SqliteTaskStore::in_memory().unwrap()));
+        tasks.retention = Some(Duration::from_secs(3600));
+        let now = unix_time(SystemTime::now());
+        let (recent, old, ancient) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for (id, completed_at) in [(recent, now - 10), (old, now - 5400), (ancient, now - 9000)] {
+            tasks.store.insert(id, "{}", 0).unwrap();
+            tasks.tasks.insert(id, finished_task(id, completed_at));
+        }
+
+        tasks.evict();
+        assert!(matches!(tasks.get(recent), Some(TaskStatus::Cancelled)));
+        assert!(matches!(tasks.get(old), Some(TaskStatus::Expired)));
+        assert!(tasks.get(ancient).is_none());
+        assert_eq!(tasks.expired.len(), 1);
+        assert_eq!(tasks.store.load().unwrap().len(), 2);
+
+        // the expired tasks are capped like the finished tasks
+        tasks.retention = None;
+        tasks.max_finished = Some(0);
+        tasks.evict();
+        assert!(tasks.get(recent).is_none());
+        assert!(tasks.get(old).is_none());
+        assert!(tasks.expired.is_empty());
+        assert!(tasks.store.load().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_matching_tasks() {
+        let mut tasks = new_tasks(Box::new(VolatileTaskStore));
+        let ids: Vec<_> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for (index, &id) in ids.iter().enumerate() {
+            let mut task = Task::new(id, TaskStatus::Submitted, Some(index as u64), None);
+            task.guest = Some(if index < 3 { "Concrete" } else { "Building" }.into());
+            task.snark = Some(index == 1);
+            tasks.tasks.insert(id, task);
+        }
+        tasks.set_status(ids[2], TaskStatus::InProgress);
+
+        let list = |query: Value| {
+            let query: TaskQuery = serde_json::from_value(query).unwrap();
+            let page = tasks.list(&query);
+            let ids = page.tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+            (page.total, ids)
+        };
+        assert_eq!(list(json!({})), (4, ids.clone()));
+        assert_eq!(
+            list(json!({"zktype": "Concrete", "limit": 2})),
+            (3, ids[..2].to_vec())
+        );
+        assert_eq!(
+            list(json!({"zktype": "Concrete", "offset": 2})),
+            (3, vec![ids[2]])
+        );
+        assert_eq!(list(json!({"snark": true})), (1, vec![ids[1]]));
+        assert_eq!(list(json!({"state": "InProgress"})), (1, vec![ids[2]]));
+        assert_eq!(
+            list(json!({"submitted_after": 1, "submitted_before": 3})),
+            (2, ids[1..3].to_vec())
+        );
+        assert!(tasks.tasks[&ids[2]].started_at.is_some());
+    }
+
+    #[test]
+    fn cache_proofs_with_stats() {
+        let dir = std::env::temp_dir().join(format!("proof-cache-{}", Uuid::new_v4()));
+        let cache = crate::cache::FsProofCache::new(&dir);
+        let key = CacheKey {
+            image_id: "aa".into(),
+            input_digest: "bb".into(),
+            receipt_kind: "Fake".into(),
+        };
+        let journal = vec![1, 2, 3];
+        let claim = ReceiptClaim::ok([0u32; 8], journal.clone());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+        let stats = ProofStats {
+            user_cycles: 1,
+            total_cycles: 2,
+            segments: 1,
+            elapsed_ms: 3,
+            dev_mode: true,
+            cached: false,
+        };
+
+        assert!(cached_proof(&cache, &key).is_none());
+        cache_proof(&cache, &key, &(receipt.clone(), stats));
+        let (cached, cached_stats) = cached_proof(&cache, &key).unwrap();
+        assert_eq!(cached.journal, receipt.journal);
+        assert!(cached_stats.cached);
+        assert_eq!(cached_stats.elapsed_ms, 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// zkEPD that only consists of its receipt.
+    #[derive(Debug)]
+    struct ReceiptOnly(Receipt);
+
+    impl VerifyConfig for ReceiptOnly {
+        fn verify(&self, _dev_mode: crate::verify::DevMode) -> Result<(), AppError> {
+            Ok(())
+        }
+
+        fn receipt(&self) -> Result<Receipt, AppError> {
+            Ok(self.0.clone())
+        }
+
+        fn with_receipt(&self, receipt: &Receipt) -> Result<Value, ProvingError> {
+            Ok(json!({ "zkp": encode_receipt(receipt)? }))
+        }
+    }
+
+    #[test]
+    fn compress_fake_receipts_in_dev_mode() {
+        let journal = vec![1, 2, 3];
+        let claim = ReceiptClaim::ok([0u32; 8], journal.clone());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+        let config = ReceiptOnly(receipt.clone());
+
+        let id = Uuid::new_v4();
+        let result = compress_epd(&config, &Cancellation::new(id), true).unwrap();
+        assert_eq!(result.zk_epd()["zkp"], *encode_receipt(&receipt).unwrap());
+        assert!(result.salt().is_none());
+    }
+
+    #[tokio::test]
+    async fn move_queued_tasks_forward() {
+        let mut tasks = new_tasks(Box::new(VolatileTaskStore));
+        let mut pool = WorkerPool::new(1, 2, true, None);
+        let claim = ReceiptClaim::ok([0u32; 8], Vec::new());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), Vec::new());
+        let job = |id| Job {
+            id,
+            work: Work::Compress(Box::new(ReceiptOnly(receipt.clone()))),
+            cancellation: Cancellation::new(id),
+        };
+        let ids: Vec<_> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let submit = |tasks: &mut Tasks, pool: &mut WorkerPool, id| {
+            let status = pool.submit(job(id));
+            tasks.tasks.insert(id, Task::new(id, status, Some(0), None));
+        };
+        for &id in &ids[..3] {
+            submit(&mut tasks, &mut pool, id);
+        }
+        assert!(matches!(tasks.get(ids[0]), Some(TaskStatus::InProgress)));
+        assert!(matches!(tasks.get(ids[1]), Some(TaskStatus::Queued(1))));
+        assert!(matches!(tasks.get(ids[2]), Some(TaskStatus::Queued(2))));
+        assert!(pool.is_full());
+
+        // a cancelled task leaves the queue, the tasks behind it move forward
+        pool.remove(ids[1]);
+        tasks.update_positions(&pool);
+        assert!(matches!(tasks.get(ids[2]), Some(TaskStatus::Queued(1))));
+        assert!(!pool.is_full());
+        submit(&mut tasks, &mut pool, ids[3]);
+        assert!(matches!(tasks.get(ids[3]), Some(TaskStatus::Queued(2))));
+
+        // the first queued task starts when a worker is free
+        assert_eq!(pool.finish(), Some(ids[2]));
+        tasks.update_positions(&pool);
+        assert!(matches!(tasks.get(ids[3]), Some(TaskStatus::Queued(1))));
+        assert_eq!(pool.finish(), Some(ids[3]));
+        assert_eq!(pool.finish(), None);
+        assert_eq!(pool.running, 0);
+    }
+
+    /// Request with an idempotency `key` or `reuse`, and a random salt if `salt` is `None`.
+    fn proof_request(key: Option<&str>, salt: Option<u8>, amount: u32) -> ProofRequest {
+        let salt_bytes = [salt.unwrap_or_default(); 32];
+        let request = json!({
+            "zk_type": "Concrete",
+            "snark_p": false,
+            "salt": salt_bytes,
+            "random_salt": salt.is_none(),
+            "dataset": null,
+            "format": "json",
+            "idempotency_key": key,
+            "reuse": key.is_none(),
+            "body": {"amount": amount},
+        });
+        serde_json::from_value(request).unwrap()
+    }
+
+    #[test]
+    fn list_task_parameters() {
+        assert_eq!(
+            proof_request(Some("key"), Some(1), 1).task_parameters(),
+            ["Idempotency-Key"]
+        );
+        assert_eq!(proof_request(None, None, 1).task_parameters(), ["reuse"]);
+    }
+
+    #[test]
+    fn cache_only_explicit_salts() {
+        assert!(proof_request(None, Some(1), 1).cacheable());
+        assert!(!proof_request(None, None, 1).cacheable());
+        assert!(!proof_request(Some("key"), None, 1).cacheable());
+    }
+
+    #[tokio::test]
+    async fn find_repeated_requests() {
+        let mut tasks = new_tasks(Box::new(VolatileTaskStore));
+        let first = Uuid::new_v4();
+        tasks.submit(first, &proof_request(Some("key"), None, 1));
+
+        assert_eq!(
+            tasks.find(&proof_request(Some("key"), None, 1)).unwrap(),
+            Some(first)
+        );
+        assert!(matches!(
+            tasks.find(&proof_request(Some("key"), None, 2)),
+            Err(AppError::IdempotencyKeyReused)
+        ));
+        assert_eq!(
+            tasks.find(&proof_request(Some("other"), None, 1)).unwrap(),
+            None
+        );
+        // the result of a task with a random salt reveals the salt, so it is never shared
+        assert_eq!(tasks.find(&proof_request(None, None, 1)).unwrap(), None);
+        assert_eq!(tasks.find(&proof_request(None, Some(0), 1)).unwrap(), None);
+        let salted = Uuid::new_v4();
+        tasks.submit(salted, &proof_request(None, Some(1), 1));
+        assert_eq!(
+            tasks.find(&proof_request(None, Some(1), 1)).unwrap(),
+            Some(salted)
+        );
+        assert_eq!(tasks.find(&proof_request(None, Some(2), 1)).unwrap(), None);
+        assert_eq!(tasks.find(&proof_request(None, None, 1)).unwrap(), None);
+
+        tasks.cancel(first).unwrap();
+        assert_eq!(tasks.find(&proof_request(None, None, 1)).unwrap(), None);
+        assert_eq!(
+            tasks.find(&proof_request(Some("key"), None, 1)).unwrap(),
+            Some(first)
+        );
+        tasks.cancel(first).unwrap();
+        assert_eq!(
+            tasks.find(&proof_request(Some("key"), None, 2)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn keep_only_summary_of_finished_tasks() {
+        let mut tasks = new_tasks(Box::new(SqliteTaskStore::in_memory().unwrap()));
+        let id = Uuid::new_v4();
+        let request = proof_request(Some("key"), None, 7);
+        tasks.submit(id, &request);
+        assert!(tasks.store.load().unwrap()[0].request.contains("\"body\""));
+
+        tasks.cancel(id).unwrap();
+        let stored = &tasks.store.load().unwrap()[0];
+        assert!(!stored.request.contains("\"body\""));
+        assert!(!stored.request.contains("\"salt\""));
+
+        // the summary still identifies repeated requests after a restart
+        let store = std::mem::replace(&mut tasks.store, Box::new(VolatileTaskStore));
+        let mut restored = new_tasks(store);
+        restored
+            .restore(&ConfigFactoryMap::new(Vec::new()), &None)
+            .unwrap();
+        assert_eq!(restored.find(&request).unwrap(), Some(id));
+        assert_eq!(restored.tasks[&id].guest.as_deref(), Some("Concrete"));
     }
 }
@@ -3,23 +3,33 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use crate::proving::{new_config as proof_config, ProofConfig, ProofInput, ProofResponse};
-use crate::verify::{new_config as verify_config, VerifyConfig};
+use crate::credentials::{CredentialError, CredentialVerifier, IssuerKey};
+use crate::datasets::{DatasetInfo, ParameterSource};
+use crate::dids::{DidError, PresentationLoader};
+use crate::proving::{new_config as proof_config, ProofConfig, ProofInput, Work};
+use crate::webhooks::Webhooks;
+use crate::verify::{
+    decode_receipt, new_config as verify_config, new_opening_config as opening_config,
+    VerifyConfig,
+};
 use axum::body::Bytes;
 use axum::extract::rejection::{BytesRejection, JsonRejection, QueryRejection};
 use axum::extract::{FromRef, FromRequest, FromRequestParts, Query, Request};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::{async_trait, Json};
+use hex::FromHex;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use risc0_zkvm::sha::Digest;
+use sha2::{Digest as _, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::convert::identity;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use thiserror::Error;
-use zk_epdcalc_core::VerifiedEpd;
+use zk_epdcalc_core::{CommittedEpd, ComposedEpd, ComposedInput, ImageId, Salt, VerifiedEpd};
 
 #[derive(Error, Debug)]
 pub enum RequestRejection {
@@ -31,12 +41,42 @@ pub enum RequestRejection {
     JsonRejection(#[from] JsonRejection),
     #[error("Bytes buffering Error")]
     BytesRejection(#[from] BytesRejection),
+    #[error("Unable to decode Zero Knowledge Proof of input EPD")]
+    InvalidAssumption,
+    #[error("Salt must be 32 hex encoded bytes")]
+    InvalidSalt,
+    #[error("Unknown dataset: {0}")]
+    UnknownDataset(Box<str>),
+    #[error("Invalid DPP credential: {0}")]
+    InvalidCredential(#[from] CredentialError),
+    #[error("DPP could not be resolved: {0}")]
+    UnresolvableDpp(#[from] DidError),
+    #[error("Results can not be issued as credentials without an issuer key")]
+    MissingIssuerKey,
+    #[error("Callback must be an http(s) URL of a public host")]
+    InvalidCallback,
+    #[error("Idempotency-Key must be at most 255 visible ASCII characters")]
+    InvalidIdempotencyKey,
 }
 
+/// Request header with a key chosen by the client, repeated submissions of a task with the same
+/// key return the existing task.
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
 impl IntoResponse for RequestRejection {
     fn into_response(self) -> Response {
         match self {
-            RequestRejection::UnknownZkType(_) => (StatusCode::BAD_REQUEST, self.to_string()).into_response(),
+            RequestRejection::UnknownZkType(_)
+            | RequestRejection::InvalidAssumption
+            | RequestRejection::InvalidSalt
+            | RequestRejection::UnknownDataset(_)
+            | RequestRejection::InvalidCredential(_)
+            | RequestRejection::UnresolvableDpp(_)
+            | RequestRejection::MissingIssuerKey
+            | RequestRejection::InvalidCallback
+            | RequestRejection::InvalidIdempotencyKey => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
             RequestRejection::QueryRejection(qr) => qr.into_response(),
             RequestRejection::JsonRejection(jr) => jr.into_response(),
             RequestRejection::BytesRejection(br) => br.into_response(),
@@ -51,39 +91,116 @@ pub trait ConfigFactory {
         &self,
         bytes: &Bytes,
         snark_p: bool,
+        salt: Salt,
+        dataset: Option<&str>,
+        issuer: Option<Arc<IssuerKey>>,
     ) -> Result<StoredProofConfig, RequestRejection>;
-    fn build_verify_config(&self, bytes: &Bytes) -> Result<Box<dyn VerifyConfig>, RequestRejection>;
+    /// Verifies a zkEPD, it may only be based on the registered `parameter_digests`.
+    fn build_verify_config(
+        &self,
+        bytes: &Bytes,
+        parameter_digests: Arc<HashSet<Digest>>,
+    ) -> Result<Box<dyn VerifyConfig>, RequestRejection>;
+    fn build_opening_config(
+        &self,
+        bytes: &Bytes,
+        parameter_digests: Arc<HashSet<Digest>>,
+    ) -> Result<Box<dyn VerifyConfig>, RequestRejection>;
+    fn datasets(&self) -> Vec<DatasetInfo>;
+    /// Image ID of the guest the proofs are generated with.
+    fn image_id(&self) -> &ImageId;
 }
 
 impl<I, Epd, ZkEpd> ConfigFactory for ConfigFactoryImpl<I, Epd, ZkEpd>
 where
-    I: ProofInput + DeserializeOwned + Debug + Send + Sync + 'static,
-    ZkEpd: ProofResponse + VerifiedEpd<Epd> + Serialize + Sync + Send + Debug + DeserializeOwned + 'static,
-    Epd: DeserializeOwned + Debug + Sync + Send + Eq + 'static,
+    I: ProofInput + ComposedInput + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+    ZkEpd: VerifiedEpd<Epd> + Serialize + Sync + Send + Debug + DeserializeOwned + 'static,
+    Epd: ComposedEpd + CommittedEpd + Serialize + DeserializeOwned + Debug + Sync + Send + Eq + 'static,
 {
     fn build_proof_config(
         &self,
         bytes: &Bytes,
         snark_p: bool,
+        salt: Salt,
+        dataset: Option<&str>,
+        issuer: Option<Arc<IssuerKey>>,
     ) -> Result<StoredProofConfig, RequestRejection> {
         let Json(input): Json<I> = Json::from_bytes(bytes)?;
-        let config = proof_config::<I, Epd, ZkEpd>(snark_p, self.guest_elf, input);
+        let parameters = match &self.datasets {
+            Some(datasets) => Some(datasets.get(dataset).ok_or_else(|| {
+                RequestRejection::UnknownDataset(dataset.unwrap_or("default").into())
+            })?),
+            None => match dataset {
+                Some(dataset) => Err(RequestRejection::UnknownDataset(dataset.into()))?,
+                None => None,
+            },
+        };
+        let assumptions = input
+            .assumptions()
+            .into_iter()
+            .map(decode_receipt)
+            .collect::<Result<_, _>>()
+            .or(Err(RequestRejection::InvalidAssumption))?;
+        let config = proof_config::<I, Epd, ZkEpd>(
+            snark_p,
+            self.guest_elf,
+            self.assumption_ids,
+            assumptions,
+            salt,
+            parameters,
+            issuer,
+            input,
+        );
         Ok(config)
     }
 
     fn build_verify_config(
         &self,
         bytes: &Bytes,
+        parameter_digests: Arc<HashSet<Digest>>,
     ) -> Result<Box<dyn VerifyConfig>, RequestRejection> {
         let Json(verify_epd): Json<ZkEpd> = Json::from_bytes(bytes)?;
-        let config = verify_config(self.guest_id, verify_epd);
+        let config =
+            verify_config(self.guest_id, self.assumption_ids, parameter_digests, verify_epd);
+        Ok(config)
+    }
+
+    fn build_opening_config(
+        &self,
+        bytes: &Bytes,
+        parameter_digests: Arc<HashSet<Digest>>,
+    ) -> Result<Box<dyn VerifyConfig>, RequestRejection> {
+        let Json(OpeningRequest { input, salt, epd }): Json<OpeningRequest<I, ZkEpd>> =
+            Json::from_bytes(bytes)?;
+        let salt = parse_salt(&salt)?;
+        let config = opening_config(
+            self.guest_id,
+            self.assumption_ids,
+            parameter_digests,
+            epd,
+            input,
+            salt,
+        );
         Ok(config)
     }
+
+    fn datasets(&self) -> Vec<DatasetInfo> {
+        self.datasets
+            .as_ref()
+            .map(|datasets| datasets.list())
+            .unwrap_or_default()
+    }
+
+    fn image_id(&self) -> &ImageId {
+        self.guest_id
+    }
 }
 
 struct ConfigFactoryImpl<I, Epd, ZkEpd, > {
     guest_elf: &'static [u8],
     guest_id: &'static [u32; 8],
+    assumption_ids: &'static [&'static ImageId],
+    datasets: Option<Arc<dyn ParameterSource>>,
     phantom_input: PhantomData<I>,
     phantom_zk_epd: PhantomData<ZkEpd>,
     phantom_epd: PhantomData<Epd>,
@@ -94,13 +211,58 @@ pub fn new_config_factory<I, Epd, ZkEpd>(
     guest_id: &'static [u32; 8],
 ) -> Box<dyn ConfigFactory + Send + Sync>
 where
-    I: ProofInput + DeserializeOwned + Debug + Send + Sync + 'static,
-    ZkEpd: ProofResponse + Serialize + VerifiedEpd<Epd> + Sync + Send + Debug + DeserializeOwned + 'static,
-    Epd: DeserializeOwned + Debug + Sync + Send + Eq + 'static,
+    I: ProofInput + ComposedInput + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+    ZkEpd: Serialize + VerifiedEpd<Epd> + Sync + Send + Debug + DeserializeOwned + 'static,
+    Epd: ComposedEpd + CommittedEpd + Serialize + DeserializeOwned + Debug + Sync + Send + Eq + 'static,
+{
+    new_composed_config_factory::<I, Epd, ZkEpd>(guest_elf, guest_id, &[])
+}
+
+/// Creates a factory for a guest that reads a public parameter dataset before its input.
+///
+/// Requests select the dataset with the `dataset` query parameter, otherwise the default dataset
+/// of `datasets` is used.
+pub fn new_parameterized_config_factory<I, Epd, ZkEpd>(
+    guest_elf: &'static [u8],
+    guest_id: &'static [u32; 8],
+    datasets: Arc<dyn ParameterSource>,
+) -> Box<dyn ConfigFactory + Send + Sync>
+where
+    I: ProofInput + ComposedInput + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+    ZkEpd: Serialize + VerifiedEpd<Epd> + Sync + Send + Debug + DeserializeOwned + 'static,
+    Epd: ComposedEpd + CommittedEpd + Serialize + DeserializeOwned + Debug + Sync + Send + Eq + 'static,
+{
+    let config_factory: ConfigFactoryImpl<I, Epd, ZkEpd> = ConfigFactoryImpl {
+        guest_elf,
+        guest_id,
+        assumption_ids: &[],
+        datasets: Some(datasets),
+        phantom_input: Default::default(),
+        phantom_zk_epd: Default::default(),
+        phantom_epd: Default::default(),
+    };
+    Box::new(config_factory)
+}
+
+/// Creates a factory for a guest that verifies receipts of other guests.
+///
+/// The `assumption_ids` are written to the guest before the input and have to match the image
+/// IDs the guest commits to its journal.
+pub fn new_composed_config_factory<I, Epd, ZkEpd>(
+    guest_elf: &'static [u8],
+    guest_id: &'static [u32; 8],
+    assumption_ids: &'static [&'static ImageId],
+) -> Box<dyn ConfigFactory + Send + Sync>
+where
+    I: ProofInput + ComposedInput + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+    ZkEpd: Serialize + VerifiedEpd<Epd> + Sync + Send + Debug + DeserializeOwned + 'static,
+    Epd: ComposedEpd + CommittedEpd + Serialize + DeserializeOwned + Debug + Sync + Send + Eq + 'static,
 {
     let config_factory: ConfigFactoryImpl<I, Epd, ZkEpd> = ConfigFactoryImpl {
         guest_elf,
         guest_id,
+        assumption_ids,
+        datasets: None,
         phantom_input: Default::default(),
         phantom_zk_epd: Default::default(),
         phantom_epd: Default::default(),
@@ -111,12 +273,39 @@ where
 #[derive(Clone)]
 pub(crate) struct ConfigFactoryMap {
     map: Arc<HashMap<&'static str, Box<dyn ConfigFactory + Sync + Send>>>,
+    /// Digests of the datasets of all guests, the only parameters a verified zkEPD (or the zkEPDs
+    /// it is composed of) may be based on.
+    parameter_digests: Arc<HashSet<Digest>>,
 }
 
 impl ConfigFactoryMap {
     pub(crate) fn new(mapping: Vec<(&'static str, Box<dyn ConfigFactory + Sync + Send>)>) -> Self {
-        let map = Arc::new(mapping.into_iter().collect());
-        Self { map }
+        let map: HashMap<_, _> = mapping.into_iter().collect();
+        let parameter_digests = map
+            .values()
+            .flat_map(|factory| factory.datasets())
+            .map(|dataset| dataset.digest)
+            .collect();
+        Self {
+            map: Arc::new(map),
+            parameter_digests: Arc::new(parameter_digests),
+        }
+    }
+
+    pub(crate) fn datasets(&self) -> HashMap<&'static str, Vec<DatasetInfo>> {
+        self.map
+            .iter()
+            .map(|(&zk_type, factory)| (zk_type, factory.datasets()))
+            .filter(|(_, datasets)| !datasets.is_empty())
+            .collect()
+    }
+
+    /// Hex encoded image IDs of the guests, as in the keys of the proof cache.
+    pub(crate) fn image_ids(&self) -> Vec<Box<str>> {
+        self.map
+            .values()
+            .map(|factory| Digest::from(*factory.image_id()).to_string().into())
+            .collect()
     }
 }
 
@@ -125,14 +314,223 @@ struct CreateParams {
     snark: Option<bool>,
     #[serde(rename = "zktype")]
     zk_type: Box<str>,
+    salt: Option<Box<str>>,
+    dataset: Option<Box<str>>,
+    #[serde(default)]
+    format: OutputFormat,
+    /// URL the status and result of an asynchronous task are posted to when it is finished.
+    callback: Option<Box<str>>,
+    /// Posts the salt of the input commitment to the callback too.
+    #[serde(default)]
+    callback_salt: bool,
+    /// Returns an existing task with the same content instead of starting a new one.
+    #[serde(default)]
+    reuse: bool,
+}
+
+/// Representation of a proven EPD in the response.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    /// The zkEPD as plain JSON.
+    #[default]
+    Json,
+    /// The zkEPD as signed verifiable credential, in the shape of a DID mapping of a proof
+    /// request.
+    Vc,
+}
+
+/// Whether a task proves a new zkEPD or compresses an existing one.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestKind {
+    #[default]
+    Prove,
+    /// The body is a zkEPD whose receipt is compressed to Groth16.
+    Compress,
+}
+
+/// Private input and salt that open the input commitment of a proven EPD.
+#[derive(Deserialize)]
+struct OpeningRequest<I, ZkEpd> {
+    input: I,
+    salt: Box<str>,
+    epd: ZkEpd,
+}
+
+fn parse_salt(salt: &str) -> Result<Salt, RequestRejection> {
+    Salt::from_hex(salt).or(Err(RequestRejection::InvalidSalt))
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<Box<str>>, RequestRejection> {
+    let Some(key) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= 255 => Ok(Some(key.into())),
+        _ => Err(RequestRejection::InvalidIdempotencyKey),
+    }
+}
+
+fn check_callback(webhooks: &Webhooks, callback: &str) -> Result<(), RequestRejection> {
+    match webhooks.is_allowed(callback) {
+        true => Ok(()),
+        false => Err(RequestRejection::InvalidCallback),
+    }
+}
+
+/// Proof request of `create`/`creation` after its DPPs were loaded and verified.
+///
+/// Asynchronous tasks store their request, so they can be resumed after a restart.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ProofRequest {
+    zk_type: Box<str>,
+    snark_p: bool,
+    salt: Salt,
+    /// `true` if the salt was not chosen by the client.
+    #[serde(default)]
+    random_salt: bool,
+    dataset: Option<Box<str>>,
+    format: OutputFormat,
+    #[serde(default)]
+    callback: Option<Box<str>>,
+    /// `true` if the salt is posted to the callback.
+    #[serde(default)]
+    callback_salt: bool,
+    #[serde(default)]
+    idempotency_key: Option<Box<str>>,
+    /// Only used when the task is submitted, it is not stored.
+    #[serde(default, skip_serializing)]
+    reuse: bool,
+    #[serde(default)]
+    kind: RequestKind,
+    body: serde_json::Value,
+}
+
+impl ProofRequest {
+    pub(crate) fn zk_type(&self) -> &str {
+        &self.zk_type
+    }
+
+    pub(crate) fn callback(&self) -> Option<&str> {
+        self.callback.as_deref()
+    }
+
+    pub(crate) fn callback_salt(&self) -> bool {
+        self.callback_salt
+    }
+
+    pub(crate) fn snark_p(&self) -> bool {
+        self.snark_p
+    }
+
+    pub(crate) fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    /// `true` if an existing task with the same [`fingerprint`](Self::fingerprint) may be
+    /// returned instead of starting a new one. Only requests with an explicit salt are reused, the
+    /// result of a task reveals its salt.
+    pub(crate) fn reuse(&self) -> bool {
+        self.reuse && !self.random_salt
+    }
+
+    /// Given parameters that only apply to tasks (`callback`, `callback_salt`, `Idempotency-Key`
+    /// and `reuse`), e.g. to reject them in a preflight.
+    pub(crate) fn task_parameters(&self) -> Vec<&'static str> {
+        [
+            ("callback", self.callback.is_some()),
+            ("callback_salt", self.callback_salt),
+            ("Idempotency-Key", self.idempotency_key.is_some()),
+            ("reuse", self.reuse),
+        ]
+        .into_iter()
+        .filter_map(|(parameter, given)| given.then_some(parameter))
+        .collect()
+    }
+
+    /// `true` if the proof may be taken from and stored in the proof cache. A random salt never
+    /// matches another request, so only proofs of requests with an explicit salt are cached.
+    pub(crate) fn cacheable(&self) -> bool {
+        !self.random_salt
+    }
+
+    /// Digest of the content that determines the result. A random salt is left out, so the retry
+    /// of a request with the same idempotency key matches.
+    pub(crate) fn fingerprint(&self) -> [u8; 32] {
+        let salt = (!self.random_salt).then_some(&self.salt);
+        let content = (
+            self.kind,
+            &self.zk_type,
+            self.snark_p,
+            salt,
+            &self.dataset,
+            &self.format,
+            &self.body,
+        );
+        let content = serde_json::to_vec(&content).expect("Request could not be serialized!");
+        Sha256::digest(content).into()
+    }
+
+    pub(crate) fn build_config(
+        &self,
+        config_map: &ConfigFactoryMap,
+        issuer_key: Option<Arc<IssuerKey>>,
+    ) -> Result<Box<dyn ProofConfig>, RequestRejection> {
+        let issuer = match self.format {
+            OutputFormat::Json => None,
+            OutputFormat::Vc => Some(issuer_key.ok_or(RequestRejection::MissingIssuerKey)?),
+        };
+        let factory = config_map
+            .map
+            .get(&*self.zk_type)
+            .ok_or_else(|| RequestRejection::UnknownZkType(self.zk_type.clone()))?;
+        let bytes = serde_json::to_vec(&self.body).expect("Request could not be serialized!");
+        factory.build_proof_config(
+            &Bytes::from(bytes),
+            self.snark_p,
+            self.salt,
+            self.dataset.as_deref(),
+            issuer,
+        )
+    }
+
+    /// The proof or compression of the request.
+    pub(crate) fn build_work(
+        &self,
+        config_map: &ConfigFactoryMap,
+        issuer_key: Option<Arc<IssuerKey>>,
+    ) -> Result<Work, RequestRejection> {
+        match self.kind {
+            RequestKind::Prove => Ok(Work::Prove {
+                config: self.build_config(config_map, issuer_key)?,
+                cacheable: self.cacheable(),
+            }),
+            RequestKind::Compress => {
+                let factory = config_map
+                    .map
+                    .get(&*self.zk_type)
+                    .ok_or_else(|| RequestRejection::UnknownZkType(self.zk_type.clone()))?;
+                let bytes =
+                    serde_json::to_vec(&self.body).expect("Request could not be serialized!");
+                Ok(Work::Compress(factory.build_verify_config(
+                    &Bytes::from(bytes),
+                    config_map.parameter_digests.clone(),
+                )?))
+            }
+        }
+    }
 }
 
-pub(crate) struct ExtractConfig(pub Box<dyn ProofConfig>);
+pub(crate) struct ExtractConfig(pub Box<dyn ProofConfig>, pub ProofRequest);
 
 #[async_trait]
 impl<OuterState> FromRequest<OuterState> for ExtractConfig
 where
     ConfigFactoryMap: FromRef<OuterState>,
+    CredentialVerifier: FromRef<OuterState>,
+    PresentationLoader: FromRef<OuterState>,
+    Option<Arc<IssuerKey>>: FromRef<OuterState>,
+    Webhooks: FromRef<OuterState>,
     OuterState: Send + Sync,
 {
     type Rejection = RequestRejection;
@@ -144,6 +542,117 @@ where
         let Query(params): Query<CreateParams> =
             Query::from_request_parts(&mut parts, state).await?;
         let snark_p = params.snark.is_some_and(identity);
+        let salt = match params.salt.as_deref() {
+            Some(salt) => parse_salt(salt)?,
+            None => rand::random(),
+        };
+        if !config_map.map.contains_key(params.zk_type.as_ref()) {
+            Err(RequestRejection::UnknownZkType(params.zk_type.clone()))?;
+        }
+        if let Some(callback) = params.callback.as_deref() {
+            check_callback(&Webhooks::from_ref(state), callback)?;
+        }
+        let idempotency_key = idempotency_key(&parts.headers)?;
+
+        let req = Request::from_parts(parts, body);
+        let bytes = Bytes::from_request(req, state).await?;
+
+        // the DPPs have to be verified before anything is proven about them
+        let Json(mut body): Json<serde_json::Value> = Json::from_bytes(&bytes)?;
+        PresentationLoader::from_ref(state)
+            .complete_request(&mut body)
+            .await?;
+        CredentialVerifier::from_ref(state)
+            .verify_request(&body)
+            .await?;
+
+        let request = ProofRequest {
+            zk_type: params.zk_type,
+            snark_p,
+            salt,
+            random_salt: params.salt.is_none(),
+            dataset: params.dataset,
+            format: params.format,
+            callback: params.callback,
+            callback_salt: params.callback_salt,
+            idempotency_key,
+            reuse: params.reuse,
+            kind: RequestKind::Prove,
+            body,
+        };
+        let config = request.build_config(&config_map, Option::from_ref(state))?;
+        Ok(Self(config, request))
+    }
+}
+
+/// zkEPD of a compression task, together with the request the task is stored with.
+pub(crate) struct ExtractCompression(pub Box<dyn VerifyConfig>, pub ProofRequest);
+
+#[async_trait]
+impl<OuterState> FromRequest<OuterState> for ExtractCompression
+where
+    ConfigFactoryMap: FromRef<OuterState>,
+    Webhooks: FromRef<OuterState>,
+    OuterState: Send + Sync,
+{
+    type Rejection = RequestRejection;
+
+    async fn from_request(req: Request, state: &OuterState) -> Result<Self, Self::Rejection> {
+        let config_map = ConfigFactoryMap::from_ref(state);
+        let (mut parts, body) = req.into_parts();
+
+        let Query(params): Query<CreateParams> =
+            Query::from_request_parts(&mut parts, state).await?;
+        if let Some(callback) = params.callback.as_deref() {
+            check_callback(&Webhooks::from_ref(state), callback)?;
+        }
+        let idempotency_key = idempotency_key(&parts.headers)?;
+
+        let req = Request::from_parts(parts, body);
+        let bytes = Bytes::from_request(req, state).await?;
+
+        let factory = config_map
+            .map
+            .get(params.zk_type.as_ref())
+            .ok_or_else(|| RequestRejection::UnknownZkType(params.zk_type.clone()))?;
+        let config = factory.build_verify_config(&bytes, config_map.parameter_digests.clone())?;
+        let Json(body): Json<serde_json::Value> = Json::from_bytes(&bytes)?;
+
+        let request = ProofRequest {
+            zk_type: params.zk_type,
+            snark_p: true,
+            // the salt of the zkEPD is not needed for its compression
+            salt: Salt::default(),
+            random_salt: true,
+            dataset: None,
+            format: OutputFormat::Json,
+            callback: params.callback,
+            callback_salt: false,
+            idempotency_key,
+            reuse: params.reuse,
+            kind: RequestKind::Compress,
+            body,
+        };
+        Ok(Self(config, request))
+    }
+}
+
+pub struct Verify(pub Box<dyn VerifyConfig>);
+
+#[async_trait]
+impl<OuterState> FromRequest<OuterState> for Verify
+where
+    ConfigFactoryMap: FromRef<OuterState>,
+    OuterState: Send + Sync,
+{
+    type Rejection = RequestRejection;
+
+    async fn from_request(req: Request, state: &OuterState) -> Result<Self, Self::Rejection> {
+        let config_map = ConfigFactoryMap::from_ref(state);
+        let (mut parts, body) = req.into_parts();
+
+        let Query(params): Query<CreateParams> =
+            Query::from_request_parts(&mut parts, state).await?;
         let zk_type = params.zk_type.as_ref();
 
         let req = Request::from_parts(parts, body);
@@ -153,15 +662,15 @@ where
             .map
             .get(zk_type)
             .ok_or(RequestRejection::UnknownZkType(params.zk_type))?;
-        let config = factory.build_proof_config(&bytes, snark_p)?;
+        let config = factory.build_verify_config(&bytes, config_map.parameter_digests.clone())?;
         Ok(Self(config))
     }
 }
 
-pub struct Verify(pub Box<dyn VerifyConfig>);
+pub struct Open(pub Box<dyn VerifyConfig>);
 
 #[async_trait]
-impl<OuterState> FromRequest<OuterState> for Verify
+impl<OuterState> FromRequest<OuterState> for Open
 where
     ConfigFactoryMap: FromRef<OuterState>,
     OuterState: Send + Sync,
@@ -183,7 +692,7 @@ where
             .map
             .get(zk_type)
             .ok_or(RequestRejection::UnknownZkType(params.zk_type))?;
-        let config = factory.build_verify_config(&bytes)?;
+        let config = factory.build_opening_config(&bytes, config_map.parameter_digests.clone())?;
         Ok(Self(config))
     }
 }
@@ -196,5 +705,7 @@ mod tests {
     fn add_build() {
         #[derive(Deserialize)]
         struct TestStruct {}
+
+        let _: TestStruct = serde_json::from_str("{}").unwrap();
     }
 }
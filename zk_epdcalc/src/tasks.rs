@@ -0,0 +1,235 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Persists the tasks of the proving service, so they survive a restart of the service.
+///
+/// Requests and results are stored as JSON strings. The request of an unfinished task contains
+/// the private input and salt, it is replaced by a summary without them when the task finishes.
+/// The store is called from the task of the proving service, so its operations should be fast.
+pub trait TaskStore: Send {
+    /// Stores a submitted task with the request it was created from and the time it was
+    /// submitted (seconds since the Unix epoch).
+    fn insert(&mut self, id: Uuid, request: &str, submitted_at: u64) -> Result<()>;
+
+    /// Stores the result of a task and the time it was completed, its request is replaced by the
+    /// `summary` that is kept of it. The result of an expired task is replaced, so the store only
+    /// keeps a small record of it.
+    fn complete(&mut self, id: Uuid, summary: &str, result: &str, completed_at: u64) -> Result<()>;
+
+    /// Removes a task, e.g. when it is deleted by the client.
+    fn remove(&mut self, id: Uuid) -> Result<()>;
+
+    /// All stored tasks, in the order they were submitted.
+    fn load(&mut self) -> Result<Vec<StoredTask>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredTask {
+    pub id: Uuid,
+    pub request: Box<str>,
+    /// `None` if the task was not completed before the service stopped.
+    pub result: Option<Box<str>>,
+    /// `None` for tasks stored before the submission time was recorded.
+    pub submitted_at: Option<u64>,
+    pub completed_at: Option<u64>,
+}
+
+/// Task store that keeps nothing, the tasks are lost on a restart.
+#[derive(Default)]
+pub struct VolatileTaskStore;
+
+impl TaskStore for VolatileTaskStore {
+    fn insert(&mut self, _id: Uuid, _request: &str, _submitted_at: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn complete(
+        &mut self,
+        _id: Uuid,
+        _summary: &str,
+        _result: &str,
+        _completed_at: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove(&mut self, _id: Uuid) -> Result<()> {
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Vec<StoredTask>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Task store in an SQLite database file.
+pub struct SqliteTaskStore {
+    connection: Connection,
+}
+
+impl SqliteTaskStore {
+    /// Opens the database, it is created if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let connection = Connection::open(path)
+            .with_context(|| format!("Unable to open task database {}", path.display()))?;
+        Self::new(connection)
+    }
+
+    /// Task store that is not persisted, e.g. for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(connection: Connection) -> Result<Self> {
+        // replaced requests are overwritten in the file, not just marked as free
+        connection.execute_batch("PRAGMA secure_delete = ON;")?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tasks (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                id TEXT NOT NULL UNIQUE,
+                request TEXT NOT NULL,
+                result TEXT,
+                completed_at INTEGER,
+                submitted_at INTEGER
+            );",
+        )?;
+        // databases created before the submission time was recorded
+        if connection
+            .prepare("SELECT submitted_at FROM tasks LIMIT 0")
+            .is_err()
+        {
+            connection.execute_batch("ALTER TABLE tasks ADD COLUMN submitted_at INTEGER;")?;
+        }
+        Ok(Self { connection })
+    }
+}
+
+impl TaskStore for SqliteTaskStore {
+    fn insert(&mut self, id: Uuid, request: &str, submitted_at: u64) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO tasks (id, request, submitted_at) VALUES (?1, ?2, ?3)",
+            params![id.to_string(), request, submitted_at],
+        )?;
+        Ok(())
+    }
+
+    fn complete(&mut self, id: Uuid, summary: &str, result: &str, completed_at: u64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE tasks SET request = ?2, result = ?3, completed_at = ?4 WHERE id = ?1",
+            params![id.to_string(), summary, result, completed_at],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&mut self, id: Uuid) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM tasks WHERE id = ?1", params![id.to_string()])?;
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Vec<StoredTask>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, request, result, submitted_at, completed_at FROM tasks ORDER BY seq",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<u64>>(3)?,
+                row.get::<_, Option<u64>>(4)?,
+            ))
+        })?;
+        rows.map(|row| {
+            let (id, request, result, submitted_at, completed_at) = row?;
+            Ok(StoredTask {
+                id: id
+                    .parse()
+                    .with_context(|| format!("Invalid task id {id}"))?,
+                request: request.into(),
+                result: result.map(Into::into),
+                submitted_at,
+                completed_at,
+            })
+        })
+        .collect()
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub(crate) fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_remove_tasks() {
+        let mut store = SqliteTaskStore::in_memory().unwrap();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        store
+            .insert(first, r#"{"zk_type":"Concrete"}"#, 90)
+            .unwrap();
+        store
+            .insert(second, r#"{"zk_type":"Building"}"#, 95)
+            .unwrap();
+        store
+            .complete(
+                first,
+                r#"{"zk_type":"Concrete"}"#,
+                r#"{"Complete":{}}"#,
+                100,
+            )
+            .unwrap();
+
+        let tasks = store.load().unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].id, first);
+        assert_eq!(tasks[0].result.as_deref(), Some(r#"{"Complete":{}}"#));
+        assert_eq!(tasks[0].submitted_at, Some(90));
+        assert_eq!(tasks[0].completed_at, Some(100));
+        assert_eq!(tasks[1].result, None);
+
+        store.complete(first, "{}", r#""Expired""#, 100).unwrap();
+        store.remove(second).unwrap();
+        let tasks = store.load().unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].result.as_deref(), Some(r#""Expired""#));
+        assert_eq!(&*tasks[0].request, "{}");
+        store.remove(first).unwrap();
+        assert!(store.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn add_submission_time_to_old_databases() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE tasks (
+                    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                    id TEXT NOT NULL UNIQUE,
+                    request TEXT NOT NULL,
+                    result TEXT,
+                    completed_at INTEGER
+                );",
+            )
+            .unwrap();
+        let mut store = SqliteTaskStore::new(connection).unwrap();
+        let id = Uuid::new_v4();
+        store.insert(id, "{}", 42).unwrap();
+        assert_eq!(store.load().unwrap()[0].submitted_at, Some(42));
+    }
+}
@@ -3,18 +3,60 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
-use crate::error::AppError;
+use crate::error::{AppError, ProvingError};
+use crate::proving::encode_receipt;
 use anyhow::Result;
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine;
-use risc0_zkvm::Receipt;
+use risc0_zkvm::sha::{Digest, Digestible};
+use risc0_zkvm::{InnerReceipt, MaybePruned, Receipt, ReceiptClaim};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
-use zk_epdcalc_core::VerifiedEpd;
+use std::sync::Arc;
+use zk_epdcalc_core::{commit_input, CommittedEpd, ComposedEpd, ImageId, Salt, VerifiedEpd};
+
+pub(crate) fn decode_receipt(zkp: &str) -> Result<Receipt, AppError> {
+    let receipt_cbor = BASE64_STANDARD
+        .decode(zkp)
+        .or(Err(AppError::ProofDecodingFailed))?;
+    ciborium::de::from_reader(receipt_cbor.as_slice()).or(Err(AppError::ProofDecodingFailed))
+}
+
+/// Whether fake receipts of guests that were only executed are accepted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DevMode(pub bool);
+
+/// Verifies that `receipt` proves a successful execution of the guest `image_id`.
+///
+/// Fake receipts are only accepted in dev mode, they have to claim the guest and journal.
+pub(crate) fn verify_receipt(
+    receipt: &Receipt,
+    image_id: [u32; 8],
+    DevMode(dev_mode): DevMode,
+) -> Result<(), AppError> {
+    match &receipt.inner {
+        InnerReceipt::Fake(fake) if dev_mode => {
+            let expected = ReceiptClaim::ok(image_id, MaybePruned::Pruned(receipt.journal.digest()));
+            if fake.claim.digest() == expected.digest() {
+                Ok(())
+            } else {
+                Err(AppError::InvalidProof)
+            }
+        }
+        InnerReceipt::Fake(_) => Err(AppError::FakeReceipt),
+        _ => receipt.verify(image_id).or(Err(AppError::InvalidProof)),
+    }
+}
 
 struct VerifyConfigImpl<Epd, ZkEpd> {
     guest_id: &'static [u32; 8],
+    assumption_ids: &'static [&'static ImageId],
+    /// Digests of the registered parameter datasets, proofs based on other parameters are invalid.
+    parameter_digests: Arc<HashSet<Digest>>,
     zk_epd: ZkEpd,
     phantom_data: PhantomData<Epd>
 }
@@ -30,13 +72,18 @@ where
     }
 }
 
-pub fn new_config<Epd, ZkEpd>(guest_id: &'static [u32; 8], zk_epd: ZkEpd) -> Box<dyn VerifyConfig>
+pub fn new_config<Epd, ZkEpd>(
+    guest_id: &'static [u32; 8],
+    assumption_ids: &'static [&'static ImageId],
+    parameter_digests: Arc<HashSet<Digest>>,
+    zk_epd: ZkEpd,
+) -> Box<dyn VerifyConfig>
 where
-    ZkEpd: VerifiedEpd<Epd> + Debug + Send + Sync + 'static,
-    Epd: Eq + Debug + Send + Sync + DeserializeOwned + 'static
+    ZkEpd: VerifiedEpd<Epd> + Serialize + Debug + Send + Sync + 'static,
+    Epd: ComposedEpd + Eq + Debug + Send + Sync + DeserializeOwned + 'static
 {
     let config: VerifyConfigImpl<Epd, ZkEpd> = VerifyConfigImpl {
-        guest_id, zk_epd, phantom_data: Default::default()
+        guest_id, assumption_ids, parameter_digests, zk_epd, phantom_data: Default::default()
     };
     Box::new(config)
 }
@@ -44,25 +91,192 @@ where
 
 impl<Epd, ZkEpd> VerifyConfig for VerifyConfigImpl<Epd, ZkEpd>
 where
-    ZkEpd: VerifiedEpd<Epd> + Debug + Send + Sync,
-    Epd: Send + Eq + Debug + DeserializeOwned
+    ZkEpd: VerifiedEpd<Epd> + Serialize + Debug + Send + Sync,
+    Epd: ComposedEpd + Send + Eq + Debug + DeserializeOwned
 {
-    fn verify(&self) -> Result<(), AppError> {
-        let zkp = self.zk_epd.get_zkp();
-        let receipt_cbor = BASE64_STANDARD.decode(zkp)
-            .or(Err(AppError::ProofDecodingFailed))?;
-        let receipt: Receipt =
-            ciborium::de::from_reader(receipt_cbor.as_slice())
-                .or(Err(AppError::ProofDecodingFailed))?;
+    fn verify(&self, dev_mode: DevMode) -> Result<(), AppError> {
+        let receipt = decode_receipt(self.zk_epd.get_zkp())?;
         let epd: Epd = receipt.journal.decode().or(Err(AppError::ProofDecodingFailed))?;
-        if epd == *self.zk_epd.get_epd() {
-            receipt.verify(*self.guest_id).or(Err(AppError::InvalidProof))
+        if epd != *self.zk_epd.get_epd() {
+            return Err(AppError::NonMatchingEPDInfo);
+        }
+        let assumption_ids = self.assumption_ids.iter().map(|&&image_id| image_id);
+        if !epd.assumption_image_ids().into_iter().eq(assumption_ids) {
+            return Err(AppError::UnknownAssumptionImage);
+        }
+        let parameter_digests = epd.parameter_digests();
+        if !parameter_digests.iter().all(|digest| self.parameter_digests.contains(digest)) {
+            return Err(AppError::UnknownParameters);
+        }
+        verify_receipt(&receipt, *self.guest_id, dev_mode)
+    }
+
+    fn receipt(&self) -> Result<Receipt, AppError> {
+        decode_receipt(self.zk_epd.get_zkp())
+    }
+
+    fn with_receipt(&self, receipt: &Receipt) -> Result<Value, ProvingError> {
+        let epd: Epd = receipt.journal.decode()?;
+        let zk_epd = ZkEpd::from_result(epd, encode_receipt(receipt)?);
+        Ok(serde_json::to_value(zk_epd)?)
+    }
+}
+
+struct OpeningConfigImpl<I, Epd, ZkEpd> {
+    verify_config: VerifyConfigImpl<Epd, ZkEpd>,
+    input: I,
+    salt: Salt,
+}
+
+impl<I, Epd, ZkEpd> Debug for OpeningConfigImpl<I, Epd, ZkEpd>
+where
+    ZkEpd: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpeningConfig")
+            .field("epd", &self.verify_config.zk_epd)
+            .finish()
+    }
+}
+
+/// Creates a config that verifies the EPD and opens its input commitment with `input` and `salt`.
+pub fn new_opening_config<I, Epd, ZkEpd>(
+    guest_id: &'static [u32; 8],
+    assumption_ids: &'static [&'static ImageId],
+    parameter_digests: Arc<HashSet<Digest>>,
+    zk_epd: ZkEpd,
+    input: I,
+    salt: Salt,
+) -> Box<dyn VerifyConfig>
+where
+    I: Serialize + Send + 'static,
+    ZkEpd: VerifiedEpd<Epd> + Serialize + Debug + Send + Sync + 'static,
+    Epd: ComposedEpd + CommittedEpd + Eq + Debug + Send + Sync + DeserializeOwned + 'static
+{
+    let verify_config: VerifyConfigImpl<Epd, ZkEpd> = VerifyConfigImpl {
+        guest_id, assumption_ids, parameter_digests, zk_epd, phantom_data: Default::default()
+    };
+    Box::new(OpeningConfigImpl { verify_config, input, salt })
+}
+
+impl<I, Epd, ZkEpd> VerifyConfig for OpeningConfigImpl<I, Epd, ZkEpd>
+where
+    I: Serialize + Send,
+    ZkEpd: VerifiedEpd<Epd> + Serialize + Debug + Send + Sync,
+    Epd: ComposedEpd + CommittedEpd + Send + Eq + Debug + DeserializeOwned
+{
+    fn verify(&self, dev_mode: DevMode) -> Result<(), AppError> {
+        self.verify_config.verify(dev_mode)?;
+        let commitment = commit_input(&self.salt, &self.input)
+            .or(Err(AppError::NonMatchingCommitment))?;
+        if commitment == *self.verify_config.zk_epd.get_epd().input_commitment() {
+            Ok(())
         } else {
-            Err(AppError::NonMatchingEPDInfo)
+            Err(AppError::NonMatchingCommitment)
         }
     }
+
+    fn receipt(&self) -> Result<Receipt, AppError> {
+        self.verify_config.receipt()
+    }
+
+    fn with_receipt(&self, receipt: &Receipt) -> Result<Value, ProvingError> {
+        self.verify_config.with_receipt(receipt)
+    }
 }
 
 pub trait VerifyConfig: Debug + Send{
-    fn verify(&self) -> Result<(), AppError>;
-}
\ No newline at end of file
+    fn verify(&self, dev_mode: DevMode) -> Result<(), AppError>;
+
+    /// The receipt of the zkEPD.
+    fn receipt(&self) -> Result<Receipt, AppError>;
+
+    /// The zkEPD with another receipt of the same journal, e.g. the compressed receipt.
+    fn with_receipt(&self, receipt: &Receipt) -> Result<Value, ProvingError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use risc0_zkvm::FakeReceipt;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct TestEpd {
+        factors_digest: Digest,
+    }
+
+    impl ComposedEpd for TestEpd {
+        fn parameter_digests(&self) -> Vec<Digest> {
+            vec![self.factors_digest]
+        }
+    }
+
+    #[derive(Serialize, Debug)]
+    struct ZkTestEpd {
+        epd: TestEpd,
+        zkp: Box<str>,
+    }
+
+    impl VerifiedEpd<TestEpd> for ZkTestEpd {
+        fn get_zkp(&self) -> &str {
+            &self.zkp
+        }
+
+        fn get_epd(&self) -> &TestEpd {
+            &self.epd
+        }
+
+        fn from_result(epd: TestEpd, zkp: Box<str>) -> Self {
+            Self { epd, zkp }
+        }
+    }
+
+    #[test]
+    fn reject_unregistered_parameters() {
+        static IMAGE_ID: ImageId = [1, 2, 3, 4, 5, 6, 7, 8];
+        let epd = TestEpd {
+            factors_digest: Digest::from([9; 8]),
+        };
+        let journal: Vec<u8> = risc0_zkvm::serde::to_vec(&epd)
+            .unwrap()
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        let claim = ReceiptClaim::ok(IMAGE_ID, journal.clone());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+        let zkp = encode_receipt(&receipt).unwrap();
+        let config = |registered: &[Digest]| {
+            let epd = TestEpd {
+                factors_digest: epd.factors_digest,
+            };
+            let zk_epd = ZkTestEpd::from_result(epd, zkp.clone());
+            let registered = Arc::new(registered.iter().copied().collect());
+            new_config(&IMAGE_ID, &[], registered, zk_epd)
+        };
+
+        assert!(config(&[epd.factors_digest]).verify(DevMode(true)).is_ok());
+        assert!(matches!(
+            config(&[Digest::from([8; 8])]).verify(DevMode(true)),
+            Err(AppError::UnknownParameters)
+        ));
+    }
+
+    #[test]
+    fn fake_receipts_require_dev_mode() {
+        let image_id = [1, 2, 3, 4, 5, 6, 7, 8];
+        let journal = vec![1, 0, 0, 0];
+        let claim = ReceiptClaim::ok(image_id, journal.clone());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+
+        assert!(matches!(
+            verify_receipt(&receipt, image_id, DevMode(false)),
+            Err(AppError::FakeReceipt)
+        ));
+        assert!(verify_receipt(&receipt, image_id, DevMode(true)).is_ok());
+        assert!(matches!(
+            verify_receipt(&receipt, [0; 8], DevMode(true)),
+            Err(AppError::InvalidProof)
+        ));
+    }
+}
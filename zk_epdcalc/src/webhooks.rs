@@ -0,0 +1,266 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use crate::handlers::EPDTaskStatus;
+use crate::outbound::OutboundClient;
+use crate::proving::TaskStatus;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Header with the HMAC-SHA256 of the callback body, as `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "x-signature-256";
+
+/// Settings of the callbacks of finished tasks.
+#[derive(Clone)]
+pub struct WebhookOptions {
+    /// Key of the HMAC in the [`SIGNATURE_HEADER`], callbacks are not signed without a secret.
+    pub secret: Option<Box<[u8]>>,
+    /// Number of attempts to deliver a callback.
+    pub max_attempts: u32,
+    /// Delay before the first retry, it is doubled after each failed attempt.
+    pub initial_backoff: Duration,
+    /// Hosts callbacks may be posted to although they are loopback, private or link-local
+    /// addresses, e.g. services in the same network. Callbacks to other such addresses are
+    /// rejected.
+    pub allowed_hosts: Vec<Box<str>>,
+}
+
+impl Default for WebhookOptions {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+/// Body of a callback, the status of the task together with its result.
+#[derive(Serialize)]
+struct CallbackBody<'a> {
+    #[serde(flatten)]
+    status: EPDTaskStatus,
+    result: Option<&'a Value>,
+    /// Hex encoded salt of the input commitment, only if the client asked for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+}
+
+/// Posts the status of finished tasks to their callback URL.
+#[derive(Clone)]
+pub(crate) struct Webhooks {
+    client: OutboundClient,
+    options: Arc<WebhookOptions>,
+}
+
+impl Webhooks {
+    pub(crate) fn new(options: WebhookOptions) -> Self {
+        Self {
+            client: OutboundClient::new(options.allowed_hosts.clone()),
+            options: Arc::new(options),
+        }
+    }
+
+    /// `true` if callbacks may be posted to the `url`, see [`OutboundClient::is_allowed`].
+    pub(crate) fn is_allowed(&self, url: &str) -> bool {
+        self.client.is_allowed(url)
+    }
+
+    /// Delivers the callback in the background, retrying failed attempts with backoff. The salt
+    /// of the result is only sent with `include_salt`.
+    pub(crate) fn notify(&self, url: &str, id: Uuid, status: &TaskStatus, include_salt: bool) {
+        let result = match status {
+            TaskStatus::Complete(result) => Some(result.clone()),
+            _ => None,
+        };
+        let body = CallbackBody {
+            status: EPDTaskStatus::new(id, status.clone()),
+            result: result.as_ref().map(|result| result.zk_epd()),
+            salt: result
+                .as_ref()
+                .filter(|_| include_salt)
+                .and_then(|result| result.salt().map(hex::encode)),
+        };
+        let body = serde_json::to_vec(&body).expect("Callback could not be serialized!");
+        let webhooks = self.clone();
+        let url = url.to_owned();
+        tokio::spawn(async move {
+            if let Err(err) = webhooks.deliver(&url, body).await {
+                tracing::warn!("Callback of task {id} to {url} failed: {err}");
+            }
+        });
+    }
+
+    async fn deliver(&self, url: &str, body: Vec<u8>) -> Result<()> {
+        let client = self.client.client_for(url).await?;
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.options.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+        let request = request.body(body);
+
+        let mut backoff = self.options.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            let response = request
+                .try_clone()
+                .expect("Callback body is not a stream")
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match response {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt >= self.options.max_attempts => return Err(err.into()),
+                Err(err) => tracing::warn!("Callback attempt {attempt} to {url} failed: {err}"),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// Value of the [`SIGNATURE_HEADER`].
+fn signature(secret: &[u8], body: &[u8]) -> String {
+    format!("sha256={}", hex::encode(hmac_sha256(secret, body)))
+}
+
+/// HMAC-SHA256 as specified in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn hmac_test_vectors() {
+        // RFC 4231, test cases 2 and 6
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex::encode(hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    /// Local HTTP stub that records the callbacks and fails the first attempt.
+    #[derive(Clone)]
+    struct Stub {
+        attempts: Arc<AtomicU32>,
+        received: mpsc::UnboundedSender<(Option<String>, String)>,
+    }
+
+    async fn receive(State(stub): State<Stub>, headers: HeaderMap, body: String) -> StatusCode {
+        if stub.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .map(|value| value.to_str().unwrap().to_owned());
+        let _ = stub.received.send((signature, body));
+        StatusCode::OK
+    }
+
+    #[tokio::test]
+    async fn retry_failed_callbacks() {
+        let (received, mut rx) = mpsc::unbounded_channel();
+        let stub = Stub {
+            attempts: Default::default(),
+            received,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/callback", listener.local_addr().unwrap());
+        let router = Router::new()
+            .route("/callback", post(receive))
+            .with_state(stub.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let webhooks = Webhooks::new(WebhookOptions {
+            secret: Some(b"secret".as_slice().into()),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            allowed_hosts: vec!["127.0.0.1".into()],
+        });
+        let id = Uuid::new_v4();
+        webhooks.notify(&url, id, &TaskStatus::Cancelled, false);
+
+        let (received_signature, body) = rx.recv().await.unwrap();
+        assert_eq!(
+            received_signature,
+            Some(signature(b"secret", body.as_bytes()))
+        );
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["id"], id.to_string());
+        assert_eq!(body["state"], "Cancelled");
+        assert_eq!(body["result"], Value::Null);
+        assert!(body.get("salt").is_none());
+        assert_eq!(stub.attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn reject_internal_callbacks() {
+        let webhooks = Webhooks::new(WebhookOptions::default());
+        for url in [
+            "ftp://example.com/callback",
+            "http://127.0.0.1:8080/callback",
+            "http://localhost/callback",
+            "http://localhost./callback",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.1.2.3/callback",
+            "http://192.168.0.1/callback",
+            "http://[::1]/callback",
+            "http://[::ffff:127.0.0.1]/callback",
+            "http://[fd00::1]/callback",
+        ] {
+            assert!(!webhooks.is_allowed(url), "{url} is allowed");
+        }
+        assert!(webhooks.is_allowed("https://example.com/callback"));
+        assert!(webhooks.is_allowed("http://93.184.215.14/callback"));
+
+        let webhooks = Webhooks::new(WebhookOptions {
+            allowed_hosts: vec!["localhost".into()],
+            ..Default::default()
+        });
+        assert!(webhooks.is_allowed("http://localhost:8080/callback"));
+        assert!(!webhooks.is_allowed("http://127.0.0.1:8080/callback"));
+    }
+}
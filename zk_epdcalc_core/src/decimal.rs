@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) 2024 Thomas Preindl
+ * MIT License (see LICENSE or https://mit-license.org)
+ */
+
+use serde::de::{Error, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::iter::Sum;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Signed fixed-point decimal with [`Decimal::DIGITS`] fractional digits.
+///
+/// The value is stored as an integer number of millionths, so addition, subtraction and
+/// multiplication by integers are exact and host and guest compute bit-identical results.
+///
+/// Rounding rules:
+/// - The product of two decimals is rounded to [`Decimal::DIGITS`] fractional digits, with ties
+///   rounded away from zero.
+/// - JSON numbers with a fraction are converted from `f64` with the same rule. JSON strings
+///   (e.g. `"3.9"`) are parsed exactly and must not have more than [`Decimal::DIGITS`] fractional
+///   digits.
+///
+/// The operators panic on overflow, use the `checked_*` functions to handle it.
+///
+/// In JSON a decimal is serialized as string, in the zkVM as its `i64` number of millionths.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal(i64);
+
+impl Decimal {
+    pub const DIGITS: u32 = 6;
+    pub const SCALE: i64 = 10i64.pow(Self::DIGITS);
+    pub const ZERO: Decimal = Decimal(0);
+
+    /// Creates the decimal `mantissa * 10^-scale`, e.g. `Decimal::new(39, 1)` is `3.9`.
+    pub const fn new(mantissa: i64, scale: u32) -> Self {
+        assert!(scale <= Self::DIGITS, "Decimal scale too large");
+        Self(mantissa * 10i64.pow(Self::DIGITS - scale))
+    }
+
+    pub const fn from_int(value: i64) -> Self {
+        Self::new(value, 0)
+    }
+
+    /// Number of millionths of the decimal.
+    pub const fn units(self) -> i64 {
+        self.0
+    }
+
+    pub const fn from_units(units: i64) -> Self {
+        Self(units)
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    /// Exact multiplication with an integer amount.
+    pub fn checked_mul_int(self, rhs: i64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(Self)
+    }
+
+    /// Sum of the decimals, `None` if the sum overflows.
+    pub fn checked_sum(iter: impl IntoIterator<Item = Self>) -> Option<Self> {
+        iter.into_iter()
+            .try_fold(Self::ZERO, |sum, value| sum.checked_add(value))
+    }
+
+    /// Multiplication rounded to [`Decimal::DIGITS`] fractional digits, ties away from zero.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let product = self.0 as i128 * rhs.0 as i128;
+        let scale = Self::SCALE as i128;
+        let mut quotient = product / scale;
+        if (product % scale).abs() * 2 >= scale {
+            quotient += product.signum();
+        }
+        i64::try_from(quotient).ok().map(Self)
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        let units = (value * Self::SCALE as f64).round();
+        if units.is_finite() && units >= i64::MIN as f64 && units <= i64::MAX as f64 {
+            Some(Self(units as i64))
+        } else {
+            None
+        }
+    }
+}
+
+impl Add for Decimal {
+    type Output = Decimal;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs).expect("Decimal addition overflowed")
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Decimal;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs).expect("Decimal subtraction overflowed")
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Decimal;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.checked_mul(rhs).expect("Decimal multiplication overflowed")
+    }
+}
+
+impl Mul<u32> for Decimal {
+    type Output = Decimal;
+
+    fn mul(self, rhs: u32) -> Self::Output {
+        self.checked_mul_int(rhs.into())
+            .expect("Decimal multiplication overflowed")
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Decimal;
+
+    fn neg(self) -> Self::Output {
+        Self(self.0.checked_neg().expect("Decimal negation overflowed"))
+    }
+}
+
+impl Sum for Decimal {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let units = self.0.unsigned_abs();
+        let scale = Self::SCALE as u64;
+        let (integer, fraction) = (units / scale, units % scale);
+        if fraction == 0 {
+            write!(f, "{sign}{integer}")
+        } else {
+            let fraction = format!("{fraction:0width$}", width = Self::DIGITS as usize);
+            write!(f, "{sign}{integer}.{}", fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDecimalError;
+
+impl Display for ParseDecimalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid decimal, expected at most {} fractional digits",
+            Decimal::DIGITS
+        )
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if integer.is_empty()
+            || fraction.len() > Self::DIGITS as usize
+            || !all_digits(integer)
+            || !all_digits(fraction)
+        {
+            return Err(ParseDecimalError);
+        }
+
+        let integer: i64 = integer.parse().or(Err(ParseDecimalError))?;
+        let fraction = format!("{fraction:0<width$}", width = Self::DIGITS as usize);
+        let fraction: i64 = fraction.parse().or(Err(ParseDecimalError))?;
+        let units = integer
+            .checked_mul(Self::SCALE)
+            .and_then(|units| units.checked_add(fraction))
+            .ok_or(ParseDecimalError)?;
+        Ok(Self(if negative { -units } else { units }))
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_i64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(DecimalVisitor)
+        } else {
+            i64::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a decimal number or string")
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        v.checked_mul(Decimal::SCALE)
+            .map(Decimal)
+            .ok_or(E::custom("decimal out of range"))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        i64::try_from(v)
+            .map_err(|_| E::custom("decimal out of range"))
+            .and_then(|v| self.visit_i64(v))
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        Decimal::from_f64(v).ok_or(E::custom("decimal out of range"))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        assert_eq!("3.9".parse(), Ok(Decimal::new(39, 1)));
+        assert_eq!("-0.005".parse(), Ok(Decimal::new(-5, 3)));
+        assert_eq!("1000".parse(), Ok(Decimal::from_int(1000)));
+        assert_eq!("0.0000001".parse::<Decimal>(), Err(ParseDecimalError));
+        assert_eq!("1.".parse(), Ok(Decimal::from_int(1)));
+        assert!(".5".parse::<Decimal>().is_err());
+        assert_eq!(Decimal::new(-1005, 3).to_string(), "-1.005");
+        assert_eq!(Decimal::from_int(12).to_string(), "12");
+    }
+
+    #[test]
+    fn multiplication_rounds_half_away_from_zero() {
+        let half_unit = Decimal::new(5, 1);
+        assert_eq!(Decimal::from_units(1) * half_unit, Decimal::from_units(1));
+        assert_eq!(Decimal::from_units(-1) * half_unit, Decimal::from_units(-1));
+        assert_eq!(Decimal::from_units(1) * Decimal::new(4, 1), Decimal::ZERO);
+        assert_eq!(Decimal::new(124, 0) * Decimal::new(12, 1), Decimal::new(1488, 1));
+        assert_eq!(Decimal::from_units(i64::MAX).checked_mul(Decimal::from_int(2)), None);
+    }
+
+    #[test]
+    fn checked_sum_detects_overflow() {
+        let values = [Decimal::from_int(1), Decimal::new(5, 1)];
+        assert_eq!(Decimal::checked_sum(values), Some(Decimal::new(15, 1)));
+        let values = [Decimal::from_units(i64::MAX), Decimal::from_units(1)];
+        assert_eq!(Decimal::checked_sum(values), None);
+    }
+
+    #[test]
+    fn json_representation() {
+        let decimal: Decimal = serde_json::from_str("0.009").unwrap();
+        assert_eq!(decimal, Decimal::new(9, 3));
+        let decimal: Decimal = serde_json::from_str("124").unwrap();
+        assert_eq!(decimal, Decimal::from_int(124));
+        let decimal: Decimal = serde_json::from_str(r#""161.485""#).unwrap();
+        assert_eq!(serde_json::to_string(&decimal).unwrap(), r#""161.485""#);
+    }
+}
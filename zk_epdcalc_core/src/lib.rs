@@ -3,10 +3,108 @@
  * MIT License (see LICENSE or https://mit-license.org)
  */
 
+use risc0_zkvm::serde::to_vec;
+use risc0_zkvm::sha::{Digest, Impl, Sha256};
+use serde::Serialize;
+
+pub use decimal::{Decimal, ParseDecimalError};
+
+mod decimal;
+
+pub type ImageId = [u32; 8];
+
+/// Prefix of the abort message of a guest whose aggregated values overflow.
+pub const AGGREGATE_OVERFLOW: &str = "Aggregate overflow";
+
+pub type Salt = [u8; 32];
+
 pub trait VerifiedEpd<Epd> {
+    /// Member of a DPP credential subject that holds the zkEPD, `None` if the zkEPD is the
+    /// subject itself.
+    const SUBJECT_MEMBER: Option<&'static str> = None;
+
     fn get_zkp(&self) -> &str;
     fn get_epd(&self) -> &Epd;
 
     fn from_result(epd: Epd, zkp: Box<str>) -> Self;
 }
 
+/// Input of a guest that verifies receipts of other guests inside the zkVM.
+pub trait ComposedInput {
+    /// Encoded receipts that are added as assumptions to the executor environment.
+    fn assumptions(&self) -> Vec<&str> {
+        Vec::new()
+    }
+}
+
+/// Public output of a guest that verifies receipts of other guests inside the zkVM.
+pub trait ComposedEpd {
+    /// Image IDs of the guests whose receipts were verified, as committed to the journal.
+    fn assumption_image_ids(&self) -> Vec<ImageId> {
+        Vec::new()
+    }
+
+    /// Digests of the public parameter datasets (e.g. emission factors) the EPD is based on, as
+    /// committed to the journal.
+    fn parameter_digests(&self) -> Vec<Digest> {
+        Vec::new()
+    }
+}
+
+/// Public output of a guest that commits to its private input.
+pub trait CommittedEpd {
+    fn input_commitment(&self) -> &Digest;
+}
+
+/// Salted SHA-256 commitment to the private input of a guest.
+///
+/// The salt is hashed followed by the input in the zkVM serialization format, so guest and host
+/// compute the same digest for the same input.
+pub fn commit_input<T: Serialize>(salt: &Salt, input: &T) -> Result<Digest, risc0_zkvm::serde::Error> {
+    hash_serialized(salt, input)
+}
+
+/// SHA-256 digest of public guest parameters (e.g. an emission factor dataset) in the zkVM
+/// serialization format.
+pub fn digest_parameters<T: Serialize>(parameters: &T) -> Result<Digest, risc0_zkvm::serde::Error> {
+    hash_serialized(&[], parameters)
+}
+
+fn hash_serialized<T: Serialize>(prefix: &[u8], data: &T) -> Result<Digest, risc0_zkvm::serde::Error> {
+    let mut bytes = prefix.to_vec();
+    bytes.extend(to_vec(data)?.iter().flat_map(|word| word.to_le_bytes()));
+    Ok(*Impl::hash_bytes(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Input {
+        cement: Decimal,
+        factory: &'static str,
+    }
+
+    #[test]
+    fn commitments_depend_on_salt_and_input() {
+        let input = Input {
+            cement: Decimal::from_int(300),
+            factory: "Wien",
+        };
+        let commitment = commit_input(&[1; 32], &input).unwrap();
+        assert_eq!(commitment, commit_input(&[1; 32], &input).unwrap());
+        assert_ne!(commitment, commit_input(&[2; 32], &input).unwrap());
+        let other = Input {
+            factory: "Eggendorf",
+            ..input
+        };
+        assert_ne!(commitment, commit_input(&[1; 32], &other).unwrap());
+        // without the salt the digest of a guessed input could be compared with the commitment
+        assert_ne!(commitment, digest_parameters(&input).unwrap());
+
+        let mut bytes = vec![1; 32];
+        bytes.extend(to_vec(&input).unwrap().iter().flat_map(|word| word.to_le_bytes()));
+        assert_eq!(commitment, *Impl::hash_bytes(&bytes));
+    }
+}